@@ -4,10 +4,11 @@ use axum::{
     extract::{Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use terra_types::offchain;
 use tokio::sync::RwLock;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing::{error, info};
@@ -61,6 +62,19 @@ struct ErrorResponse {
     error: String,
 }
 
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ContractValueKind {
+    Asset,
+    PurchaseReceipt,
+}
+
+#[derive(Debug, Deserialize)]
+struct DecodeRequest {
+    kind: ContractValueKind,
+    xdr: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct HorizonRoot {
     horizon_version: String,
@@ -100,6 +114,7 @@ async fn main() {
     let app = Router::new()
         .route("/health", get(health))
         .route("/api/stellar/network", get(get_network_status))
+        .route("/api/contracts/decode", post(decode_contract_value))
         .with_state(state)
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http());
@@ -191,6 +206,29 @@ async fn get_network_status(
     }))
 }
 
+async fn decode_contract_value(
+    Json(request): Json<DecodeRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let decoded = match request.kind {
+        ContractValueKind::Asset => offchain::decode_asset(&request.xdr).map(|asset| serde_json::json!(asset)),
+        ContractValueKind::PurchaseReceipt => {
+            offchain::decode_receipt(&request.xdr).map(|receipt| serde_json::json!(receipt))
+        }
+    };
+
+    decoded
+        .map(|data| Json(serde_json::json!({ "ok": true, "data": data })))
+        .map_err(|err| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    ok: false,
+                    error: format!("invalid contract value: {err}"),
+                }),
+            )
+        })
+}
+
 fn horizon_url(network: StellarNetwork) -> &'static str {
     match network {
         StellarNetwork::Testnet => "https://horizon-testnet.stellar.org",
@@ -2,33 +2,31 @@
 
 use soroban_sdk::auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation};
 use soroban_sdk::{
-    contract, contractclient, contractimpl, contracttype, token, vec, Address, Env, IntoVal, String,
-    Symbol, Vec,
+    contract, contractimpl, contracttype, symbol_short, token, vec, Address, BytesN, Env, Error,
+    IntoVal, Map, String, Symbol, TryFromVal, Val, Vec,
 };
 
-#[derive(Clone)]
-#[contracttype]
-pub struct Asset {
-    pub id: u64,
-    pub seller: Address,
-    pub category: String,
-    pub title: String,
-    pub price_per_token: i128,
-    pub total_tokens: i128,
-    pub available_tokens: i128,
-    pub active: bool,
-}
+pub use terra_types::{
+    Asset, AuctionError, FeedData, LifecycleStatus, MarketplaceError, OracleClient, PriceData,
+    PurchaseReceipt, TokenizationClient,
+};
 
-#[contractclient(name = "TokenizationClient")]
-pub trait TokenizationInterface {
-    fn get_asset(env: Env, asset_id: u64) -> Asset;
-    fn execute_sale(env: Env, seller: Address, buyer: Address, asset_id: u64, quantity: i128) -> i128;
+// Roles operativos: si un rol no tiene titular asignado lo ejerce el admin.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Role {
+    FeeManager,
+    NetworkManager,
+    Pauser,
 }
 
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
     Admin,
+    PendingAdmin,
+    RoleHolder(Role),
+    SchemaVersion,
     TokenizationContract,
     PaymentToken,
     PaymentTokenByNetwork(Symbol),
@@ -37,18 +35,161 @@ pub enum DataKey {
     FeeBps,
     LiquidityDestination,
     LiquidityShareBps,
+    PayoutPool(u64),
+    PayoutPosition((u64, Address)),
+    Paused,
+    AssetPaused(u64),
+    SellOrder(u64),
+    NextOrderId,
+    Offer(u64),
+    NextOfferId,
+    FundingPool(u64),
+    Contribution((u64, Address)),
+    AssetFeeBps(u64),
+    SellerFeeBps(Address),
+    FeeTiers,
+    BuyerVolume(Address),
+    ReferralShareBps,
+    // Por (referido, token): los montos de tokens distintos no se suman.
+    ReferralTotal((Address, Address)),
+    AccrualMode,
+    Accrued((Address, Address)),
+    Auction(u64),
+    NextAuctionId,
+    AuctionReserved(u64),
+    PaymentRates,
+    ValuationOracle,
 }
 
+// Orden de venta del mercado secundario. Los tokens quedan en escrow en
+// tokenizacion mientras la orden este abierta.
 #[derive(Clone)]
 #[contracttype]
-pub struct PurchaseReceipt {
+pub struct SellOrder {
+    pub id: u64,
     pub asset_id: u64,
     pub seller: Address,
+    pub quantity: i128,
+    pub price_per_token: i128,
+    pub expires_at: u64,
+}
+
+// Oferta de compra sobre la emision primaria. El pago queda bloqueado en el
+// marketplace, en el token de pago vigente al crearla, hasta que el vendedor la
+// acepte o venza.
+#[derive(Clone)]
+#[contracttype]
+pub struct Offer {
+    pub id: u64,
+    pub asset_id: u64,
     pub buyer: Address,
     pub quantity: i128,
-    pub total_paid: i128,
-    pub fee_paid: i128,
+    pub price_per_token: i128,
+    pub expires_at: u64,
+    pub payment_token: Address,
+    pub locked_amount: i128,
+}
+
+// Descuento por volumen: un comprador con compras acumuladas >= `min_volume`
+// paga como maximo `fee_bps`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeeTier {
+    pub min_volume: i128,
+    pub fee_bps: i128,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum RateSource {
+    Manual,
+    Oracle(Address, String),
+}
+
+// Cotizacion de un token de pago alternativo: `rate` son unidades minimas del
+// token por unidad minima del token base, escaladas por `RATE_SCALE`. Se rechaza
+// si tiene mas de `max_age` segundos.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PaymentRate {
+    pub source: RateSource,
+    pub rate: i128,
+    pub updated_at: u64,
+    pub max_age: u64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum AuctionKind {
+    English,
+    Dutch,
+}
+
+// Subasta de emision primaria. Precios por token; `quantity` queda reservada
+// mientras la subasta este abierta. Inglesa: `reserve_price` es la puja minima
+// y la puja ganadora queda bloqueada en el marketplace. Holandesa: el precio baja
+// de `start_price` a `reserve_price` y el primero que acepta compra todo.
+#[derive(Clone)]
+#[contracttype]
+pub struct Auction {
+    pub id: u64,
+    pub asset_id: u64,
+    pub seller: Address,
+    pub kind: AuctionKind,
+    pub quantity: i128,
+    pub start_price: i128,
+    pub reserve_price: i128,
+    pub start_at: u64,
+    pub end_at: u64,
+    pub payment_token: Address,
+    pub highest_bidder: Option<Address>,
+    pub highest_bid: i128,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum FundingStatus {
+    Open,
+    Succeeded,
+    Failed,
+}
+
+// Pagos retenidos de una ronda con meta de financiamiento, separados en lo que
+// se liberaria al vendedor y a tesoreria/liquidez si la ronda tiene exito.
+#[derive(Clone)]
+#[contracttype]
+pub struct FundingPool {
+    pub payment_token: Address,
     pub seller_amount: i128,
+    pub fee_amount: i128,
+    // Comisiones de referidos incluidas en `fee_amount`, pagadas al finalizar.
+    pub referrals: Map<Address, i128>,
+    pub status: FundingStatus,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Contribution {
+    pub quantity: i128,
+    pub paid: i128,
+    // Lo que sumo al volumen del comprador (token base); `refund` lo descuenta.
+    pub quoted_total: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PayoutPool {
+    pub token: Address,
+    pub payout_per_token: i128,
+    pub total_deposited: i128,
+    pub total_claimed: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PayoutPosition {
+    pub checkpoint: i128,
+    pub claimed: i128,
 }
 
 #[contract]
@@ -58,6 +199,17 @@ pub struct TerraMarketplace;
 impl TerraMarketplace {
     const BPS_DENOMINATOR: i128 = 10_000;
     const MAX_FEE_BPS: i128 = 2_000;
+    const PAYOUT_SCALE: i128 = 1_000_000_000_000;
+    const RATE_SCALE: i128 = 10_000_000;
+    const MAX_PAGE_SIZE: u32 = 50;
+    const MAX_CART_SIZE: u32 = 20;
+    const VERSION: u32 = 1;
+    const SCHEMA_VERSION: u32 = 1;
+    const DAY_IN_LEDGERS: u32 = 17_280;
+    const INSTANCE_BUMP_AMOUNT: u32 = 30 * Self::DAY_IN_LEDGERS;
+    const INSTANCE_LIFETIME_THRESHOLD: u32 = Self::INSTANCE_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+    const PERSISTENT_BUMP_AMOUNT: u32 = 60 * Self::DAY_IN_LEDGERS;
+    const PERSISTENT_LIFETIME_THRESHOLD: u32 = Self::PERSISTENT_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
 
     pub fn init(
         env: Env,
@@ -66,14 +218,15 @@ impl TerraMarketplace {
         payment_token: Address,
         treasury: Address,
         fee_bps: i128,
-    ) {
+    ) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
         if env.storage().instance().has(&DataKey::Admin) {
-            panic!("already initialized");
+            return Err(MarketplaceError::AlreadyInitialized);
         }
         admin.require_auth();
 
-        if fee_bps < 0 || fee_bps > Self::MAX_FEE_BPS {
-            panic!("invalid fee bps");
+        if !(0..=Self::MAX_FEE_BPS).contains(&fee_bps) {
+            return Err(MarketplaceError::InvalidFeeBps);
         }
 
         env.storage().instance().set(&DataKey::Admin, &admin);
@@ -88,277 +241,2341 @@ impl TerraMarketplace {
         env.storage().instance().set(&DataKey::Treasury, &treasury);
         env.storage().instance().set(&DataKey::FeeBps, &fee_bps);
         env.storage().instance().set(&DataKey::LiquidityShareBps, &0_i128);
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &Self::SCHEMA_VERSION);
+        env.events().publish((symbol_short!("init"),), admin);
+        Ok(())
     }
 
-    pub fn set_fee_config(env: Env, treasury: Address, fee_bps: i128) {
-        let admin = Self::get_admin(env.clone());
+    pub fn propose_admin(env: Env, new_admin: Address) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
         admin.require_auth();
+        env.storage().instance().set(&DataKey::PendingAdmin, &new_admin);
+        env.events()
+            .publish((symbol_short!("admin"), symbol_short!("proposed")), new_admin);
+        Ok(())
+    }
 
-        if fee_bps < 0 || fee_bps > Self::MAX_FEE_BPS {
-            panic!("invalid fee bps");
-        }
+    pub fn accept_admin(env: Env) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let pending = env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::PendingAdmin)
+            .ok_or(MarketplaceError::NoPendingAdmin)?;
+        pending.require_auth();
+        env.storage().instance().set(&DataKey::Admin, &pending);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+        env.events()
+            .publish((symbol_short!("admin"), symbol_short!("accepted")), pending);
+        Ok(())
+    }
 
-        env.storage().instance().set(&DataKey::Treasury, &treasury);
-        env.storage().instance().set(&DataKey::FeeBps, &fee_bps);
+    pub fn get_admin(env: Env) -> Result<Address, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::Admin)
+            .ok_or(MarketplaceError::NotInitialized)
     }
 
-    pub fn set_payment_token(env: Env, payment_token: Address) {
-        let admin = Self::get_admin(env.clone());
-        admin.require_auth();
-        env.storage().instance().set(&DataKey::PaymentToken, &payment_token);
+    pub fn get_pending_admin(env: Env) -> Option<Address> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::PendingAdmin)
     }
 
-    pub fn set_network_payment_token(env: Env, network: Symbol, payment_token: Address) {
-        let admin = Self::get_admin(env.clone());
+    pub fn set_role(env: Env, role: Role, holder: Option<Address>) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
         admin.require_auth();
-        if !Self::is_supported_network(env.clone(), network.clone()) {
-            panic!("unsupported network");
+        match holder.clone() {
+            Some(address) => env
+                .storage()
+                .instance()
+                .set(&DataKey::RoleHolder(role), &address),
+            None => env.storage().instance().remove(&DataKey::RoleHolder(role)),
         }
+        env.events().publish((symbol_short!("role"), role), holder);
+        Ok(())
+    }
+
+    pub fn get_role(env: Env, role: Role) -> Option<Address> {
+        Self::extend_instance_ttl(&env);
         env.storage()
             .instance()
-            .set(&DataKey::PaymentTokenByNetwork(network), &payment_token);
+            .get::<DataKey, Address>(&DataKey::RoleHolder(role))
     }
 
-    pub fn set_active_network(env: Env, network: Symbol) {
-        let admin = Self::get_admin(env.clone());
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
         admin.require_auth();
-        if !Self::is_supported_network(env.clone(), network.clone()) {
-            panic!("unsupported network");
+        env.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+        env.events().publish((symbol_short!("upgrade"),), new_wasm_hash);
+        Ok(())
+    }
+
+    // Se invoca despues de `upgrade` cuando el nuevo WASM cambia el formato del
+    // storage. Cada paso lleva el esquema de `n` a `n + 1`.
+    pub fn migrate(env: Env) -> Result<u32, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+
+        let from = Self::get_schema_version(env.clone());
+        if from >= Self::SCHEMA_VERSION {
+            return Err(MarketplaceError::AlreadyMigrated);
         }
-        env.storage().instance().set(&DataKey::ActiveNetwork, &network);
+
+        // v0 -> v1: despliegues previos a la llave `SchemaVersion`; no hay datos
+        // que transformar, solo se registra la version.
+
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &Self::SCHEMA_VERSION);
+        env.events()
+            .publish((symbol_short!("migrate"),), (from, Self::SCHEMA_VERSION));
+        Ok(Self::SCHEMA_VERSION)
     }
 
-    pub fn get_active_network(env: Env) -> Option<Symbol> {
-        env.storage().instance().get::<DataKey, Symbol>(&DataKey::ActiveNetwork)
+    pub fn version() -> u32 {
+        Self::VERSION
     }
 
-    pub fn get_network_payment_token(env: Env, network: Symbol) -> Option<Address> {
+    pub fn get_schema_version(env: Env) -> u32 {
+        Self::extend_instance_ttl(&env);
         env.storage()
             .instance()
-            .get::<DataKey, Address>(&DataKey::PaymentTokenByNetwork(network))
+            .get::<DataKey, u32>(&DataKey::SchemaVersion)
+            .unwrap_or(0)
     }
 
-    pub fn set_liquidity_config(env: Env, destination: Option<Address>, share_bps: i128) {
-        let admin = Self::get_admin(env.clone());
-        admin.require_auth();
-
-        if share_bps < 0 || share_bps > Self::BPS_DENOMINATOR {
-            panic!("invalid liquidity bps");
-        }
+    pub fn set_paused(env: Env, paused: bool) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::Pauser)?;
+        env.storage().instance().set(&DataKey::Paused, &paused);
+        env.events().publish((symbol_short!("paused"),), paused);
+        Ok(())
+    }
 
-        if let Some(address) = destination {
-            env.storage()
-                .instance()
-                .set(&DataKey::LiquidityDestination, &address);
-            env.storage()
-                .instance()
-                .set(&DataKey::LiquidityShareBps, &share_bps);
+    pub fn set_asset_paused(env: Env, asset_id: u64, paused: bool) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::Pauser)?;
+        if paused {
+            Self::write_persistent(&env, &DataKey::AssetPaused(asset_id), &true);
         } else {
-            env.storage().instance().remove(&DataKey::LiquidityDestination);
-            env.storage().instance().set(&DataKey::LiquidityShareBps, &0_i128);
+            env.storage().persistent().remove(&DataKey::AssetPaused(asset_id));
         }
+        env.events().publish((symbol_short!("paused"), asset_id), paused);
+        Ok(())
     }
 
-    pub fn preview_purchase(env: Env, buyer: Address, asset_id: u64, quantity: i128) -> PurchaseReceipt {
-        let tokenization = Self::get_tokenization_contract(env.clone());
-        let tokenization_client = TokenizationClient::new(&env, &tokenization);
-        let asset = tokenization_client.get_asset(&asset_id);
-        if quantity <= 0 {
-            panic!("quantity must be > 0");
-        }
-        if !asset.active {
-            panic!("asset not active");
-        }
-        if asset.available_tokens < quantity {
-            panic!("insufficient available tokens");
-        }
+    pub fn is_paused(env: Env) -> bool {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, bool>(&DataKey::Paused)
+            .unwrap_or(false)
+    }
 
-        let total = asset
-            .price_per_token
-            .checked_mul(quantity)
-            .unwrap_or_else(|| panic!("cost overflow"));
-        let fee_bps = Self::get_fee_bps(env.clone());
-        let fee = Self::calc_bps(total, fee_bps);
-        let seller_amount = total
-            .checked_sub(fee)
-            .unwrap_or_else(|| panic!("seller amount underflow"));
-
-        PurchaseReceipt {
-            asset_id,
-            seller: asset.seller,
-            buyer,
-            quantity,
-            total_paid: total,
-            fee_paid: fee,
-            seller_amount,
-        }
+    pub fn is_asset_paused(env: Env, asset_id: u64) -> bool {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent::<bool>(&env, &DataKey::AssetPaused(asset_id)).unwrap_or(false)
     }
 
-    pub fn buy_tokens(env: Env, buyer: Address, asset_id: u64, quantity: i128) -> PurchaseReceipt {
-        buyer.require_auth();
-        if quantity <= 0 {
-            panic!("quantity must be > 0");
+    pub fn set_fee_config(env: Env, treasury: Address, fee_bps: i128) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::FeeManager)?;
+
+        Self::validate_fee_bps(fee_bps)?;
+
+        env.storage().instance().set(&DataKey::Treasury, &treasury);
+        env.storage().instance().set(&DataKey::FeeBps, &fee_bps);
+        env.events().publish((symbol_short!("fee_cfg"),), (treasury, fee_bps));
+        Ok(())
+    }
+
+    // `None` quita el override y el activo vuelve a la regla siguiente.
+    pub fn set_asset_fee(env: Env, asset_id: u64, fee_bps: Option<i128>) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::FeeManager)?;
+        let key = DataKey::AssetFeeBps(asset_id);
+        match fee_bps {
+            Some(bps) => {
+                Self::validate_fee_bps(bps)?;
+                Self::write_persistent(&env, &key, &bps);
+            }
+            None => env.storage().persistent().remove(&key),
         }
+        env.events().publish(
+            (symbol_short!("fee_cfg"), symbol_short!("asset"), asset_id),
+            fee_bps,
+        );
+        Ok(())
+    }
 
-        let tokenization = Self::get_tokenization_contract(env.clone());
-        let tokenization_client = TokenizationClient::new(&env, &tokenization);
-        let asset = tokenization_client.get_asset(&asset_id);
-        if !asset.active {
-            panic!("asset not active");
+    pub fn set_seller_fee(env: Env, seller: Address, fee_bps: Option<i128>) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::FeeManager)?;
+        let key = DataKey::SellerFeeBps(seller.clone());
+        match fee_bps {
+            Some(bps) => {
+                Self::validate_fee_bps(bps)?;
+                Self::write_persistent(&env, &key, &bps);
+            }
+            None => env.storage().persistent().remove(&key),
         }
-        if asset.available_tokens < quantity {
-            panic!("insufficient available tokens");
+        env.events().publish(
+            (symbol_short!("fee_cfg"), symbol_short!("seller"), seller),
+            fee_bps,
+        );
+        Ok(())
+    }
+
+    // Tramos ordenados por `min_volume` estrictamente creciente; una lista vacia
+    // desactiva los descuentos.
+    pub fn set_fee_tiers(env: Env, tiers: Vec<FeeTier>) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::FeeManager)?;
+        let mut previous: Option<i128> = None;
+        for tier in tiers.iter() {
+            Self::validate_fee_bps(tier.fee_bps)?;
+            if tier.min_volume <= 0 || previous.is_some_and(|min| tier.min_volume <= min) {
+                return Err(MarketplaceError::InvalidFeeTiers);
+            }
+            previous = Some(tier.min_volume);
         }
+        env.storage().instance().set(&DataKey::FeeTiers, &tiers);
+        env.events()
+            .publish((symbol_short!("fee_cfg"), symbol_short!("tiers")), tiers);
+        Ok(())
+    }
 
-        let total = asset
-            .price_per_token
-            .checked_mul(quantity)
-            .unwrap_or_else(|| panic!("cost overflow"));
-        let fee_bps = Self::get_fee_bps(env.clone());
-        let fee = Self::calc_bps(total, fee_bps);
-        let seller_amount = total
-            .checked_sub(fee)
-            .unwrap_or_else(|| panic!("seller amount underflow"));
+    pub fn get_fee_tiers(env: Env) -> Vec<FeeTier> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Vec<FeeTier>>(&DataKey::FeeTiers)
+            .unwrap_or(Vec::new(&env))
+    }
 
-        let payment_token = Self::get_payment_token(env.clone());
-        let token_client = token::Client::new(&env, &payment_token);
+    // Orden de resolucion del fee que paga `buyer` al comprar `asset_id`:
+    // 1. override del activo (`set_asset_fee`),
+    // 2. si no hay, override del vendedor (`set_seller_fee`),
+    // 3. si no hay, el fee global de `set_fee_config`.
+    // Sobre ese valor se aplica el tramo de volumen mas alto que alcance el
+    // comprador (`set_fee_tiers`), solo si es menor: un tramo nunca sube el fee.
+    pub fn get_effective_fee(env: Env, asset_id: u64, buyer: Address) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&asset_id);
+        Ok(Self::resolve_fee_bps(&env, asset_id, &asset.seller, &buyer))
+    }
 
-        if seller_amount > 0 {
-            token_client.transfer(&buyer, &asset.seller, &seller_amount);
-        }
+    // Total pagado por el comprador en todas sus compras, base de los tramos.
+    pub fn get_buyer_volume(env: Env, buyer: Address) -> i128 {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::BuyerVolume(buyer)).unwrap_or(0)
+    }
 
-        if fee > 0 {
-            let treasury = Self::get_treasury(env.clone());
-            let liquidity_share_bps = Self::get_liquidity_share_bps(env.clone());
-            let liquidity_destination = env
-                .storage()
-                .instance()
-                .get::<DataKey, Address>(&DataKey::LiquidityDestination);
+    pub fn set_payment_token(env: Env, payment_token: Address) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::NetworkManager)?;
+        env.storage().instance().set(&DataKey::PaymentToken, &payment_token);
+        env.events().publish((symbol_short!("pay_token"),), payment_token);
+        Ok(())
+    }
 
-            if let Some(destination) = liquidity_destination {
-                let liquidity_amount = Self::calc_bps(fee, liquidity_share_bps);
-                let treasury_amount = fee
-                    .checked_sub(liquidity_amount)
-                    .unwrap_or_else(|| panic!("treasury amount underflow"));
+    pub fn set_network_payment_token(
+        env: Env,
+        network: Symbol,
+        payment_token: Address,
+    ) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::NetworkManager)?;
+        if !Self::is_supported_network(env.clone(), network.clone()) {
+            return Err(MarketplaceError::UnsupportedNetwork);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::PaymentTokenByNetwork(network.clone()), &payment_token);
+        env.events()
+            .publish((symbol_short!("net_token"), network), payment_token);
+        Ok(())
+    }
 
-                if treasury_amount > 0 {
-                    token_client.transfer(&buyer, &treasury, &treasury_amount);
-                }
-                if liquidity_amount > 0 {
-                    token_client.transfer(&buyer, &destination, &liquidity_amount);
-                }
-            } else {
-                token_client.transfer(&buyer, &treasury, &fee);
-            }
+    pub fn set_active_network(env: Env, network: Symbol) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::NetworkManager)?;
+        if !Self::is_supported_network(env.clone(), network.clone()) {
+            return Err(MarketplaceError::UnsupportedNetwork);
         }
+        env.storage().instance().set(&DataKey::ActiveNetwork, &network);
+        env.events().publish((symbol_short!("network"),), network);
+        Ok(())
+    }
 
-        Self::authorize_sale_call(
-            env.clone(),
-            tokenization.clone(),
-            asset.seller.clone(),
-            buyer.clone(),
-            asset_id,
-            quantity,
-        );
+    pub fn get_active_network(env: Env) -> Option<Symbol> {
+        Self::extend_instance_ttl(&env);
+        env.storage().instance().get::<DataKey, Symbol>(&DataKey::ActiveNetwork)
+    }
 
-        let _ = tokenization_client.execute_sale(&asset.seller, &buyer, &asset_id, &quantity);
+    pub fn get_network_payment_token(env: Env, network: Symbol) -> Option<Address> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::PaymentTokenByNetwork(network))
+    }
 
-        PurchaseReceipt {
-            asset_id,
-            seller: asset.seller,
-            buyer,
-            quantity,
-            total_paid: total,
-            fee_paid: fee,
-            seller_amount,
+    // Tokens de pago alternativos. Los precios siguen expresados en el token base
+    // y se convierten al cobrar con la cotizacion manual o la del oraculo.
+    pub fn set_payment_rate(
+        env: Env,
+        token: Address,
+        rate: i128,
+        max_age: u64,
+    ) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::NetworkManager)?;
+        if rate <= 0 {
+            return Err(MarketplaceError::InvalidRate);
         }
+        Self::write_payment_rate(
+            &env,
+            &token,
+            PaymentRate {
+                source: RateSource::Manual,
+                rate,
+                updated_at: env.ledger().timestamp(),
+                max_age,
+            },
+        );
+        env.events().publish(
+            (symbol_short!("pay_rate"), symbol_short!("manual"), token),
+            (rate, max_age),
+        );
+        Ok(())
     }
 
-    fn authorize_sale_call(
+    pub fn set_payment_oracle(
         env: Env,
-        tokenization_contract: Address,
-        seller: Address,
-        buyer: Address,
-        asset_id: u64,
-        quantity: i128,
-    ) {
-        let context = ContractContext {
-            contract: tokenization_contract,
-            fn_name: Symbol::new(&env, "execute_sale"),
-            args: vec![
-                &env,
-                seller.into_val(&env),
-                buyer.into_val(&env),
-                asset_id.into_val(&env),
-                quantity.into_val(&env),
-            ],
-        };
-        let entry = InvokerContractAuthEntry::Contract(SubContractInvocation {
-            context,
-            sub_invocations: Vec::new(&env),
-        });
-        env.authorize_as_current_contract(vec![&env, entry]);
+        token: Address,
+        oracle: Address,
+        feed: String,
+        max_age: u64,
+    ) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::NetworkManager)?;
+        Self::write_payment_rate(
+            &env,
+            &token,
+            PaymentRate {
+                source: RateSource::Oracle(oracle.clone(), feed.clone()),
+                rate: 0,
+                updated_at: 0,
+                max_age,
+            },
+        );
+        env.events().publish(
+            (symbol_short!("pay_rate"), symbol_short!("oracle"), token),
+            (oracle, feed, max_age),
+        );
+        Ok(())
     }
 
-    fn get_admin(env: Env) -> Address {
-        env.storage()
-            .instance()
-            .get::<DataKey, Address>(&DataKey::Admin)
-            .unwrap_or_else(|| panic!("not initialized"))
+    pub fn remove_payment_token(env: Env, token: Address) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::NetworkManager)?;
+        let mut rates = Self::get_payment_rates(env.clone());
+        if rates.remove(token.clone()).is_none() {
+            return Err(MarketplaceError::UnsupportedPaymentToken);
+        }
+        env.storage().instance().set(&DataKey::PaymentRates, &rates);
+        env.events().publish(
+            (symbol_short!("pay_rate"), symbol_short!("remove"), token),
+            (),
+        );
+        Ok(())
     }
 
-    fn get_tokenization_contract(env: Env) -> Address {
+    pub fn get_payment_rates(env: Env) -> Map<Address, PaymentRate> {
+        Self::extend_instance_ttl(&env);
         env.storage()
             .instance()
-            .get::<DataKey, Address>(&DataKey::TokenizationContract)
-            .unwrap_or_else(|| panic!("tokenization contract missing"))
+            .get::<DataKey, Map<Address, PaymentRate>>(&DataKey::PaymentRates)
+            .unwrap_or(Map::new(&env))
     }
 
-    fn get_payment_token(env: Env) -> Address {
-        if let Some(active_network) = env.storage().instance().get::<DataKey, Symbol>(&DataKey::ActiveNetwork) {
-            if let Some(token) = env
-                .storage()
-                .instance()
-                .get::<DataKey, Address>(&DataKey::PaymentTokenByNetwork(active_network))
-            {
-                return token;
-            }
+    // Cotizacion vigente de `token`; falla si no es aceptado o esta vencida.
+    pub fn get_conversion_rate(env: Env, token: Address) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::conversion_rate(&env, &token)
+    }
+
+    pub fn set_valuation_oracle(env: Env, oracle: Option<Address>) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::NetworkManager)?;
+        match oracle.clone() {
+            Some(address) => env.storage().instance().set(&DataKey::ValuationOracle, &address),
+            None => env.storage().instance().remove(&DataKey::ValuationOracle),
         }
-        env.storage()
-            .instance()
-            .get::<DataKey, Address>(&DataKey::PaymentToken)
-            .unwrap_or_else(|| panic!("payment token missing"))
+        env.events().publish((symbol_short!("val_orcl"),), oracle);
+        Ok(())
     }
 
-    fn get_treasury(env: Env) -> Address {
+    pub fn get_valuation_oracle(env: Env) -> Option<Address> {
+        Self::extend_instance_ttl(&env);
         env.storage()
             .instance()
-            .get::<DataKey, Address>(&DataKey::Treasury)
-            .unwrap_or_else(|| panic!("treasury missing"))
+            .get::<DataKey, Address>(&DataKey::ValuationOracle)
     }
 
-    fn get_fee_bps(env: Env) -> i128 {
-        env.storage()
-            .instance()
-            .get::<DataKey, i128>(&DataKey::FeeBps)
-            .unwrap_or(0)
+    // Precio y rendimiento de referencia de la categoria del activo; `None` sin
+    // oraculo configurado. Si el oraculo no tiene mediana vigente la llamada
+    // falla con su propio error.
+    pub fn get_asset_valuation(env: Env, asset_id: u64) -> Result<Option<FeedData>, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let Some(oracle) = Self::get_valuation_oracle(env.clone()) else {
+            return Ok(None);
+        };
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&asset_id);
+        Ok(Some(OracleClient::new(&env, &oracle).get_feed(&asset.category)))
     }
 
-    fn get_liquidity_share_bps(env: Env) -> i128 {
+    // Modo acumulacion: vendedores, tesoreria, liquidez y referidos cobran a un
+    // saldo interno por `(direccion, token de pago)` en vez de recibir una
+    // transferencia en cada compra.
+    pub fn set_accrual_mode(env: Env, enabled: bool) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::FeeManager)?;
+        env.storage().instance().set(&DataKey::AccrualMode, &enabled);
+        env.events().publish((symbol_short!("accrual"),), enabled);
+        Ok(())
+    }
+
+    pub fn is_accrual_mode(env: Env) -> bool {
+        Self::extend_instance_ttl(&env);
         env.storage()
             .instance()
-            .get::<DataKey, i128>(&DataKey::LiquidityShareBps)
-            .unwrap_or(0)
+            .get::<DataKey, bool>(&DataKey::AccrualMode)
+            .unwrap_or(false)
     }
 
-    fn calc_bps(amount: i128, bps: i128) -> i128 {
-        amount
-            .checked_mul(bps)
-            .unwrap_or_else(|| panic!("bps overflow"))
-            / Self::BPS_DENOMINATOR
+    pub fn get_accrued(env: Env, recipient: Address, token: Address) -> i128 {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::Accrued((recipient, token))).unwrap_or(0)
     }
 
-    fn is_supported_network(env: Env, network: Symbol) -> bool {
-        network == Symbol::new(&env, "testnet") || network == Symbol::new(&env, "mainnet")
+    // Cobra el saldo acumulado en `token`. Sigue disponible aunque despues se
+    // desactive el modo acumulacion.
+    pub fn withdraw(env: Env, recipient: Address, token: Address) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        recipient.require_auth();
+        if Self::is_paused(env.clone()) {
+            return Err(MarketplaceError::Paused);
+        }
+        let key = DataKey::Accrued((recipient.clone(), token.clone()));
+        let amount = Self::read_persistent::<i128>(&env, &key).unwrap_or(0);
+        if amount <= 0 {
+            return Err(MarketplaceError::NothingToWithdraw);
+        }
+        env.storage().persistent().remove(&key);
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &recipient, &amount);
+        env.events()
+            .publish((symbol_short!("withdraw"), recipient, token), amount);
+        Ok(amount)
     }
-}
+
+    pub fn set_liquidity_config(
+        env: Env,
+        destination: Option<Address>,
+        share_bps: i128,
+    ) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::FeeManager)?;
+
+        if !(0..=Self::BPS_DENOMINATOR).contains(&share_bps)
+            || share_bps + Self::get_referral_share_bps(env.clone()) > Self::BPS_DENOMINATOR
+        {
+            return Err(MarketplaceError::InvalidLiquidityBps);
+        }
+
+        if let Some(address) = destination.clone() {
+            env.storage()
+                .instance()
+                .set(&DataKey::LiquidityDestination, &address);
+            env.storage()
+                .instance()
+                .set(&DataKey::LiquidityShareBps, &share_bps);
+        } else {
+            env.storage().instance().remove(&DataKey::LiquidityDestination);
+            env.storage().instance().set(&DataKey::LiquidityShareBps, &0_i128);
+        }
+        let effective_share_bps = if destination.is_some() { share_bps } else { 0 };
+        env.events()
+            .publish((symbol_short!("liq_cfg"),), (destination, effective_share_bps));
+        Ok(())
+    }
+
+    // Porcion del fee que cobra quien refirio la compra. Junto con la de liquidez
+    // no puede superar el 100% del fee; tesoreria se queda con el resto.
+    pub fn set_referral_config(env: Env, share_bps: i128) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::FeeManager)?;
+        if !(0..=Self::BPS_DENOMINATOR).contains(&share_bps)
+            || share_bps + Self::get_liquidity_share_bps(env.clone()) > Self::BPS_DENOMINATOR
+        {
+            return Err(MarketplaceError::InvalidReferralBps);
+        }
+        env.storage().instance().set(&DataKey::ReferralShareBps, &share_bps);
+        env.events().publish((symbol_short!("ref_cfg"),), share_bps);
+        Ok(())
+    }
+
+    pub fn get_referral_share_bps(env: Env) -> i128 {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, i128>(&DataKey::ReferralShareBps)
+            .unwrap_or(0)
+    }
+
+    pub fn get_referral_total(env: Env, referrer: Address, token: Address) -> i128 {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::ReferralTotal((referrer, token))).unwrap_or(0)
+    }
+
+    pub fn preview_purchase(
+        env: Env,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+        referrer: Option<Address>,
+        pay_with: Address,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&asset_id);
+        let receipt = Self::quote_purchase(
+            env.clone(),
+            &asset,
+            buyer,
+            quantity,
+            asset.price_per_token,
+            referrer,
+            &Self::get_payment_token(env.clone())?,
+        )?;
+        Self::convert_receipt(&env, receipt, &pay_with)
+    }
+
+    pub fn buy_tokens(
+        env: Env,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+        referrer: Option<Address>,
+        pay_with: Address,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        buyer.require_auth();
+        Self::purchase(env, buyer, asset_id, quantity, None, referrer, pay_with)
+    }
+
+    // Igual que `buy_tokens` pero protege al comprador de cambios de precio o fee
+    // entre el preview y el envio: `max_total_paid` es el `total_paid` que
+    // devolvio `preview_purchase` (en `pay_with`) y `deadline` un timestamp del ledger.
+    #[allow(clippy::too_many_arguments)]
+    pub fn buy_tokens_with_limits(
+        env: Env,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+        max_total_paid: i128,
+        deadline: u64,
+        referrer: Option<Address>,
+        pay_with: Address,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        buyer.require_auth();
+        if env.ledger().timestamp() > deadline {
+            return Err(MarketplaceError::DeadlineExpired);
+        }
+        Self::purchase(env, buyer, asset_id, quantity, Some(max_total_paid), referrer, pay_with)
+    }
+
+    // Carrito: cotiza todas las lineas, cobra con una transferencia por
+    // destinatario y ejecuta cada venta. Si una linea falla no se compra nada.
+    pub fn buy_many(
+        env: Env,
+        buyer: Address,
+        items: Vec<(u64, i128)>,
+        max_total: i128,
+    ) -> Result<Vec<PurchaseReceipt>, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        buyer.require_auth();
+        if items.is_empty() || items.len() > Self::MAX_CART_SIZE {
+            return Err(MarketplaceError::InvalidCart);
+        }
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let tokenization_client = TokenizationClient::new(&env, &tokenization);
+        let payment_token = Self::get_payment_token(env.clone())?;
+        let mut legs: Map<Address, i128> = Map::new(&env);
+        let mut receipts: Vec<PurchaseReceipt> = Vec::new(&env);
+        let mut total: i128 = 0;
+        let mut seen: Map<u64, bool> = Map::new(&env);
+
+        for (asset_id, quantity) in items.iter() {
+            // Cada linea se cotiza contra el mismo estado del activo: dos lineas del
+            // mismo activo pasarian los controles de inventario y topes por separado.
+            if seen.contains_key(asset_id) {
+                return Err(MarketplaceError::InvalidCart);
+            }
+            seen.set(asset_id, true);
+            let asset = tokenization_client.get_asset(&asset_id);
+            let receipt = Self::quote_purchase(
+                env.clone(),
+                &asset,
+                buyer.clone(),
+                quantity,
+                asset.price_per_token,
+                None,
+                &payment_token,
+            )?;
+            total = total
+                .checked_add(receipt.total_paid)
+                .ok_or(MarketplaceError::Overflow)?;
+            if Self::hold_funding_payment(&env, &asset, &payment_token, &receipt)? {
+                Self::add_leg(&mut legs, &env.current_contract_address(), receipt.total_paid)?;
+            } else {
+                Self::add_payment_legs(
+                    &env,
+                    &mut legs,
+                    &asset.seller,
+                    receipt.fee_paid,
+                    receipt.seller_amount,
+                    &Map::new(&env),
+                )?;
+            }
+            receipts.push_back(receipt);
+        }
+        if total > max_total {
+            return Err(MarketplaceError::SlippageExceeded);
+        }
+
+        Self::pay_legs(&env, &payment_token, &buyer, &legs)?;
+        Self::record_buyer_volume(&env, &buyer, total)?;
+
+        for receipt in receipts.iter() {
+            Self::authorize_tokenization_call(
+                env.clone(),
+                tokenization.clone(),
+                "execute_sale",
+                vec![
+                    &env,
+                    receipt.seller.into_val(&env),
+                    buyer.into_val(&env),
+                    receipt.asset_id.into_val(&env),
+                    receipt.quantity.into_val(&env),
+                ],
+            );
+            let _ = tokenization_client.execute_sale(&receipt.seller, &buyer, &receipt.asset_id, &receipt.quantity);
+            env.events()
+                .publish((symbol_short!("sale"), receipt.asset_id, buyer.clone()), receipt);
+        }
+
+        Ok(receipts)
+    }
+
+    pub fn create_sell_order(
+        env: Env,
+        seller: Address,
+        asset_id: u64,
+        quantity: i128,
+        price_per_token: i128,
+        expires_at: u64,
+    ) -> Result<u64, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        seller.require_auth();
+        if quantity <= 0 || price_per_token <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+        if expires_at <= env.ledger().timestamp() {
+            return Err(MarketplaceError::InvalidExpiry);
+        }
+        Self::ensure_not_paused(&env, asset_id)?;
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        Self::authorize_tokenization_call(
+            env.clone(),
+            tokenization.clone(),
+            "escrow_tokens",
+            vec![
+                &env,
+                seller.into_val(&env),
+                asset_id.into_val(&env),
+                quantity.into_val(&env),
+            ],
+        );
+        TokenizationClient::new(&env, &tokenization).escrow_tokens(&seller, &asset_id, &quantity);
+
+        let order_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextOrderId)
+            .unwrap_or(1);
+        env.storage().instance().set(&DataKey::NextOrderId, &(order_id + 1));
+
+        let order = SellOrder {
+            id: order_id,
+            asset_id,
+            seller,
+            quantity,
+            price_per_token,
+            expires_at,
+        };
+        Self::write_persistent(&env, &DataKey::SellOrder(order_id), &order);
+        env.events().publish(
+            (symbol_short!("order"), symbol_short!("create"), asset_id),
+            order,
+        );
+        Ok(order_id)
+    }
+
+    // Llenado parcial o total: mismo reparto de fee que la venta primaria, y los
+    // tokens salen del escrow del vendedor hacia el comprador.
+    pub fn fill_sell_order(
+        env: Env,
+        buyer: Address,
+        order_id: u64,
+        quantity: i128,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        buyer.require_auth();
+        let mut order = Self::get_sell_order(env.clone(), order_id)?;
+        if quantity <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+        if order.quantity < quantity {
+            return Err(MarketplaceError::InsufficientAvailableTokens);
+        }
+        if env.ledger().timestamp() >= order.expires_at {
+            return Err(MarketplaceError::OrderExpired);
+        }
+        if buyer == order.seller {
+            return Err(MarketplaceError::SelfTrade);
+        }
+        Self::ensure_not_paused(&env, order.asset_id)?;
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let tokenization_client = TokenizationClient::new(&env, &tokenization);
+        if !tokenization_client.is_eligible(&order.asset_id, &buyer) {
+            return Err(MarketplaceError::BuyerNotVerified);
+        }
+
+        let total = order
+            .price_per_token
+            .checked_mul(quantity)
+            .ok_or(MarketplaceError::Overflow)?;
+        let fee_bps = Self::resolve_fee_bps(&env, order.asset_id, &order.seller, &buyer);
+        let (fee, seller_amount) = Self::split_total(total, fee_bps)?;
+        let payment_token = Self::get_payment_token(env.clone())?;
+        Self::distribute_payment(&env, &payment_token, &buyer, &order.seller, fee, seller_amount)?;
+        Self::record_buyer_volume(&env, &buyer, total)?;
+
+        Self::authorize_tokenization_call(
+            env.clone(),
+            tokenization.clone(),
+            "release_escrow",
+            vec![
+                &env,
+                order.seller.into_val(&env),
+                buyer.into_val(&env),
+                order.asset_id.into_val(&env),
+                quantity.into_val(&env),
+            ],
+        );
+        tokenization_client.release_escrow(&order.seller, &buyer, &order.asset_id, &quantity);
+
+        order.quantity = order
+            .quantity
+            .checked_sub(quantity)
+            .ok_or(MarketplaceError::Overflow)?;
+        if order.quantity == 0 {
+            env.storage().persistent().remove(&DataKey::SellOrder(order_id));
+        } else {
+            Self::write_persistent(&env, &DataKey::SellOrder(order_id), &order);
+        }
+
+        let receipt = PurchaseReceipt {
+            asset_id: order.asset_id,
+            seller: order.seller,
+            buyer,
+            quantity,
+            total_paid: total,
+            fee_paid: fee,
+            seller_amount,
+            referrer: None,
+            referral_amount: 0,
+            payment_token,
+            quoted_total: total,
+        };
+        env.events().publish(
+            (symbol_short!("order"), symbol_short!("fill"), order_id),
+            receipt.clone(),
+        );
+        Ok(receipt)
+    }
+
+    // El vendedor puede cancelar en cualquier momento; una orden vencida la puede
+    // cerrar cualquiera. En ambos casos el escrow vuelve al vendedor.
+    pub fn cancel_sell_order(env: Env, caller: Address, order_id: u64) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        caller.require_auth();
+        let order = Self::get_sell_order(env.clone(), order_id)?;
+        if caller != order.seller && env.ledger().timestamp() < order.expires_at {
+            return Err(MarketplaceError::OrderNotExpired);
+        }
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        Self::authorize_tokenization_call(
+            env.clone(),
+            tokenization.clone(),
+            "release_escrow",
+            vec![
+                &env,
+                order.seller.into_val(&env),
+                order.seller.into_val(&env),
+                order.asset_id.into_val(&env),
+                order.quantity.into_val(&env),
+            ],
+        );
+        TokenizationClient::new(&env, &tokenization).release_escrow(
+            &order.seller,
+            &order.seller,
+            &order.asset_id,
+            &order.quantity,
+        );
+
+        env.storage().persistent().remove(&DataKey::SellOrder(order_id));
+        env.events().publish(
+            (symbol_short!("order"), symbol_short!("cancel"), order_id),
+            (order.seller, order.quantity),
+        );
+        Ok(order.quantity)
+    }
+
+    pub fn get_sell_order(env: Env, order_id: u64) -> Result<SellOrder, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::SellOrder(order_id)).ok_or(MarketplaceError::OrderNotFound)
+    }
+
+    pub fn list_sell_orders(env: Env, from_id: u64, limit: u32) -> Map<u64, SellOrder> {
+        Self::extend_instance_ttl(&env);
+        let mut out: Map<u64, SellOrder> = Map::new(&env);
+        let max = if limit > Self::MAX_PAGE_SIZE { Self::MAX_PAGE_SIZE } else { limit };
+        let next_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextOrderId)
+            .unwrap_or(1);
+
+        let mut current = from_id;
+        let mut count: u32 = 0;
+        while current < next_id && count < max {
+            if let Some(order) = Self::read_persistent::<SellOrder>(&env, &DataKey::SellOrder(current)) {
+                out.set(current, order);
+                count += 1;
+            }
+            current += 1;
+        }
+
+        out
+    }
+
+    pub fn make_offer(
+        env: Env,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+        price_per_token: i128,
+        expires_at: u64,
+    ) -> Result<u64, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        buyer.require_auth();
+        if price_per_token <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+        if expires_at <= env.ledger().timestamp() {
+            return Err(MarketplaceError::InvalidExpiry);
+        }
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&asset_id);
+        let payment_token = Self::get_payment_token(env.clone())?;
+        let receipt = Self::quote_purchase(
+            env.clone(),
+            &asset,
+            buyer.clone(),
+            quantity,
+            price_per_token,
+            None,
+            &payment_token,
+        )?;
+
+        token::Client::new(&env, &payment_token).transfer(
+            &buyer,
+            &env.current_contract_address(),
+            &receipt.total_paid,
+        );
+
+        let offer_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextOfferId)
+            .unwrap_or(1);
+        env.storage().instance().set(&DataKey::NextOfferId, &(offer_id + 1));
+
+        let offer = Offer {
+            id: offer_id,
+            asset_id,
+            buyer,
+            quantity,
+            price_per_token,
+            expires_at,
+            payment_token,
+            locked_amount: receipt.total_paid,
+        };
+        Self::write_persistent(&env, &DataKey::Offer(offer_id), &offer);
+        env.events().publish(
+            (symbol_short!("offer"), symbol_short!("create"), asset_id),
+            offer,
+        );
+        Ok(offer_id)
+    }
+
+    // Solo el vendedor del activo acepta, y solo antes del vencimiento. Se vuelven
+    // a validar estado, inventario y KYC porque pudieron cambiar desde la oferta.
+    pub fn accept_offer(
+        env: Env,
+        seller: Address,
+        offer_id: u64,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        seller.require_auth();
+        let offer = Self::get_offer(env.clone(), offer_id)?;
+        if env.ledger().timestamp() >= offer.expires_at {
+            return Err(MarketplaceError::OfferExpired);
+        }
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&offer.asset_id);
+        if asset.seller != seller {
+            return Err(MarketplaceError::Unauthorized);
+        }
+        let receipt = Self::quote_purchase(
+            env.clone(),
+            &asset,
+            offer.buyer.clone(),
+            offer.quantity,
+            offer.price_per_token,
+            None,
+            &offer.payment_token,
+        )?;
+
+        env.storage().persistent().remove(&DataKey::Offer(offer_id));
+        Self::complete_primary_sale(
+            &env,
+            &tokenization,
+            &asset,
+            &receipt,
+            &offer.payment_token,
+            &env.current_contract_address(),
+        )?;
+
+        env.events().publish(
+            (symbol_short!("offer"), symbol_short!("accept"), offer_id),
+            receipt.clone(),
+        );
+        Ok(receipt)
+    }
+
+    // Una oferta vencida y no aceptada la puede cerrar cualquiera; el pago
+    // bloqueado vuelve siempre al comprador.
+    pub fn refund_offer(env: Env, offer_id: u64) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let offer = Self::get_offer(env.clone(), offer_id)?;
+        if env.ledger().timestamp() < offer.expires_at {
+            return Err(MarketplaceError::OfferNotExpired);
+        }
+
+        env.storage().persistent().remove(&DataKey::Offer(offer_id));
+        token::Client::new(&env, &offer.payment_token).transfer(
+            &env.current_contract_address(),
+            &offer.buyer,
+            &offer.locked_amount,
+        );
+        env.events().publish(
+            (symbol_short!("offer"), symbol_short!("refund"), offer_id),
+            (offer.buyer, offer.locked_amount),
+        );
+        Ok(offer.locked_amount)
+    }
+
+    pub fn get_offer(env: Env, offer_id: u64) -> Result<Offer, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::Offer(offer_id)).ok_or(MarketplaceError::OfferNotFound)
+    }
+
+    pub fn list_offers(env: Env, from_id: u64, limit: u32) -> Map<u64, Offer> {
+        Self::extend_instance_ttl(&env);
+        let mut out: Map<u64, Offer> = Map::new(&env);
+        let max = if limit > Self::MAX_PAGE_SIZE { Self::MAX_PAGE_SIZE } else { limit };
+        let next_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextOfferId)
+            .unwrap_or(1);
+
+        let mut current = from_id;
+        let mut count: u32 = 0;
+        while current < next_id && count < max {
+            if let Some(offer) = Self::read_persistent::<Offer>(&env, &DataKey::Offer(current)) {
+                out.set(current, offer);
+                count += 1;
+            }
+            current += 1;
+        }
+
+        out
+    }
+
+    // Cierra la ronda con escrow. Exito apenas se vende la meta (o despues del
+    // plazo si se alcanzo); fracaso solo una vez vencido el plazo. Cualquiera
+    // puede invocarla.
+    pub fn finalize_funding(env: Env, asset_id: u64) -> Result<FundingStatus, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        Self::ensure_not_paused(&env, asset_id)?;
+        let mut pool = Self::get_funding_pool(env.clone(), asset_id)
+            .ok_or(MarketplaceError::NoFundingEscrow)?;
+        if pool.status != FundingStatus::Open {
+            return Err(MarketplaceError::FundingAlreadyFinalized);
+        }
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&asset_id);
+        if asset.circulating_supply >= asset.funding_goal {
+            let mut legs: Map<Address, i128> = Map::new(&env);
+            Self::add_payment_legs(
+                &env,
+                &mut legs,
+                &asset.seller,
+                pool.fee_amount,
+                pool.seller_amount,
+                &pool.referrals,
+            )?;
+            Self::pay_legs(&env, &pool.payment_token, &env.current_contract_address(), &legs)?;
+            for (referrer, amount) in pool.referrals.iter() {
+                Self::record_referral(&env, asset_id, &referrer, &pool.payment_token, amount)?;
+            }
+            pool.status = FundingStatus::Succeeded;
+        } else if env.ledger().timestamp() >= asset.funding_deadline {
+            pool.status = FundingStatus::Failed;
+        } else {
+            return Err(MarketplaceError::FundingGoalNotReached);
+        }
+
+        Self::write_persistent(&env, &DataKey::FundingPool(asset_id), &pool);
+        env.events().publish(
+            (symbol_short!("funding"), symbol_short!("final"), asset_id),
+            (pool.status, asset.circulating_supply),
+        );
+        Ok(pool.status)
+    }
+
+    // Ronda fallida: devuelve el pago completo (fee incluido) y revierte el
+    // balance del comprador en tokenizacion.
+    pub fn refund(env: Env, buyer: Address, asset_id: u64) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        buyer.require_auth();
+        Self::ensure_not_paused(&env, asset_id)?;
+        let pool = Self::get_funding_pool(env.clone(), asset_id)
+            .ok_or(MarketplaceError::NoFundingEscrow)?;
+        if pool.status != FundingStatus::Failed {
+            return Err(MarketplaceError::FundingNotFailed);
+        }
+        let key = DataKey::Contribution((asset_id, buyer.clone()));
+        let contribution = Self::read_persistent::<Contribution>(&env, &key)
+            .ok_or(MarketplaceError::NothingToRefund)?;
+        env.storage().persistent().remove(&key);
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        Self::authorize_tokenization_call(
+            env.clone(),
+            tokenization.clone(),
+            "revert_sale",
+            vec![
+                &env,
+                buyer.into_val(&env),
+                asset_id.into_val(&env),
+                contribution.quantity.into_val(&env),
+            ],
+        );
+        let tokenization_client = TokenizationClient::new(&env, &tokenization);
+        tokenization_client.revert_sale(&buyer, &asset_id, &contribution.quantity);
+        // Con todo devuelto la ronda fallida se borra, asi el vendedor puede fijar
+        // otra meta sin que las compras choquen con `FundingFailed`.
+        if tokenization_client.get_asset(&asset_id).circulating_supply == 0 {
+            env.storage().persistent().remove(&DataKey::FundingPool(asset_id));
+        }
+
+        token::Client::new(&env, &pool.payment_token).transfer(
+            &env.current_contract_address(),
+            &buyer,
+            &contribution.paid,
+        );
+        Self::record_buyer_volume(&env, &buyer, -contribution.quoted_total)?;
+        env.events().publish(
+            (symbol_short!("funding"), symbol_short!("refund"), asset_id),
+            (buyer, contribution.quantity, contribution.paid),
+        );
+        Ok(contribution.paid)
+    }
+
+    pub fn get_funding_pool(env: Env, asset_id: u64) -> Option<FundingPool> {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::FundingPool(asset_id))
+    }
+
+    pub fn get_contribution(env: Env, asset_id: u64, buyer: Address) -> Option<Contribution> {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::Contribution((asset_id, buyer)))
+    }
+
+    pub fn create_english_auction(
+        env: Env,
+        seller: Address,
+        asset_id: u64,
+        quantity: i128,
+        reserve_price: i128,
+        end_at: u64,
+    ) -> Result<u64, Error> {
+        Self::extend_instance_ttl(&env);
+        seller.require_auth();
+        if reserve_price <= 0 {
+            return Err(AuctionError::InvalidAuction.into());
+        }
+        let auction = Auction {
+            id: 0,
+            asset_id,
+            seller,
+            kind: AuctionKind::English,
+            quantity,
+            start_price: reserve_price,
+            reserve_price,
+            start_at: env.ledger().timestamp(),
+            end_at,
+            payment_token: Self::get_payment_token(env.clone())?,
+            highest_bidder: None,
+            highest_bid: 0,
+        };
+        Ok(Self::open_auction(&env, auction)?)
+    }
+
+    pub fn create_dutch_auction(
+        env: Env,
+        seller: Address,
+        asset_id: u64,
+        quantity: i128,
+        start_price: i128,
+        floor_price: i128,
+        end_at: u64,
+    ) -> Result<u64, Error> {
+        Self::extend_instance_ttl(&env);
+        seller.require_auth();
+        if floor_price <= 0 || start_price <= floor_price {
+            return Err(AuctionError::InvalidAuction.into());
+        }
+        let auction = Auction {
+            id: 0,
+            asset_id,
+            seller,
+            kind: AuctionKind::Dutch,
+            quantity,
+            start_price,
+            reserve_price: floor_price,
+            start_at: env.ledger().timestamp(),
+            end_at,
+            payment_token: Self::get_payment_token(env.clone())?,
+            highest_bidder: None,
+            highest_bid: 0,
+        };
+        Ok(Self::open_auction(&env, auction)?)
+    }
+
+    // Puja por token en una subasta inglesa. Se bloquea el total y la puja
+    // superada queda como saldo retirable del postor anterior (`withdraw`).
+    pub fn bid(
+        env: Env,
+        bidder: Address,
+        auction_id: u64,
+        price_per_token: i128,
+    ) -> Result<(), Error> {
+        Self::extend_instance_ttl(&env);
+        bidder.require_auth();
+        let mut auction = Self::get_auction(env.clone(), auction_id)?;
+        if auction.kind != AuctionKind::English {
+            return Err(AuctionError::InvalidAuction.into());
+        }
+        if env.ledger().timestamp() >= auction.end_at {
+            return Err(AuctionError::AuctionEnded.into());
+        }
+        if bidder == auction.seller {
+            return Err(MarketplaceError::SelfTrade.into());
+        }
+        Self::ensure_not_paused(&env, auction.asset_id)?;
+        if price_per_token < auction.reserve_price
+            || (auction.highest_bidder.is_some() && price_per_token <= auction.highest_bid)
+        {
+            return Err(AuctionError::BidTooLow.into());
+        }
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        if !TokenizationClient::new(&env, &tokenization).is_eligible(&auction.asset_id, &bidder) {
+            return Err(MarketplaceError::BuyerNotVerified.into());
+        }
+
+        let total = price_per_token
+            .checked_mul(auction.quantity)
+            .ok_or(MarketplaceError::Overflow)?;
+        token::Client::new(&env, &auction.payment_token).transfer(
+            &bidder,
+            &env.current_contract_address(),
+            &total,
+        );
+        if let Some(previous) = auction.highest_bidder.clone() {
+            let previous_total = auction
+                .highest_bid
+                .checked_mul(auction.quantity)
+                .ok_or(MarketplaceError::Overflow)?;
+            Self::credit_accrued(&env, &previous, &auction.payment_token, previous_total)?;
+        }
+
+        auction.highest_bidder = Some(bidder.clone());
+        auction.highest_bid = price_per_token;
+        Self::write_persistent(&env, &DataKey::Auction(auction_id), &auction);
+        env.events().publish(
+            (symbol_short!("auction"), symbol_short!("bid"), auction_id),
+            (bidder, price_per_token),
+        );
+        Ok(())
+    }
+
+    // Compra inmediata de toda la cantidad de una subasta holandesa al precio
+    // vigente; `max_price_per_token` protege de pagar mas de lo visto.
+    pub fn take_dutch_auction(
+        env: Env,
+        buyer: Address,
+        auction_id: u64,
+        max_price_per_token: i128,
+    ) -> Result<PurchaseReceipt, Error> {
+        Self::extend_instance_ttl(&env);
+        buyer.require_auth();
+        let auction = Self::get_auction(env.clone(), auction_id)?;
+        if auction.kind != AuctionKind::Dutch {
+            return Err(AuctionError::InvalidAuction.into());
+        }
+        if env.ledger().timestamp() >= auction.end_at {
+            return Err(AuctionError::AuctionEnded.into());
+        }
+        if buyer == auction.seller {
+            return Err(MarketplaceError::SelfTrade.into());
+        }
+        let price = Self::auction_price(&env, &auction)?;
+        if price > max_price_per_token {
+            return Err(MarketplaceError::SlippageExceeded.into());
+        }
+
+        Self::close_auction(&env, &auction)?;
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&auction.asset_id);
+        let receipt = Self::quote_purchase(
+            env.clone(),
+            &asset,
+            buyer.clone(),
+            auction.quantity,
+            price,
+            None,
+            &auction.payment_token,
+        )?;
+        Self::complete_primary_sale(&env, &tokenization, &asset, &receipt, &auction.payment_token, &buyer)?;
+
+        env.events().publish(
+            (symbol_short!("auction"), symbol_short!("settled"), auction_id),
+            receipt.clone(),
+        );
+        Ok(receipt)
+    }
+
+    // Cierra una subasta vencida (cualquiera puede invocarla). Inglesa con pujas:
+    // vende al mejor postor con el reparto habitual desde los fondos bloqueados;
+    // si la venta ya no es posible (KYC vencido, activo fuera de FUNDING, etc.) el
+    // postor recupera su puja via `withdraw`. Sin venta se libera la reserva.
+    pub fn settle_auction(env: Env, auction_id: u64) -> Result<Option<PurchaseReceipt>, Error> {
+        Self::extend_instance_ttl(&env);
+        let auction = Self::get_auction(env.clone(), auction_id)?;
+        if env.ledger().timestamp() < auction.end_at {
+            return Err(AuctionError::AuctionNotEnded.into());
+        }
+        Self::ensure_not_paused(&env, auction.asset_id)?;
+        Self::close_auction(&env, &auction)?;
+
+        let Some(winner) = auction.highest_bidder.clone() else {
+            env.events().publish(
+                (symbol_short!("auction"), symbol_short!("closed"), auction_id),
+                (),
+            );
+            return Ok(None);
+        };
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&auction.asset_id);
+        let quote = Self::quote_purchase(
+            env.clone(),
+            &asset,
+            winner.clone(),
+            auction.quantity,
+            auction.highest_bid,
+            None,
+            &auction.payment_token,
+        )
+        .and_then(|receipt| {
+            Self::ensure_funding_token(&env, &asset, &auction.payment_token).map(|_| receipt)
+        });
+        let receipt = match quote {
+            Ok(receipt) => receipt,
+            Err(_) => {
+                let locked = auction
+                    .highest_bid
+                    .checked_mul(auction.quantity)
+                    .ok_or(MarketplaceError::Overflow)?;
+                Self::credit_accrued(&env, &winner, &auction.payment_token, locked)?;
+                env.events().publish(
+                    (symbol_short!("auction"), symbol_short!("closed"), auction_id),
+                    (),
+                );
+                return Ok(None);
+            }
+        };
+        Self::complete_primary_sale(
+            &env,
+            &tokenization,
+            &asset,
+            &receipt,
+            &auction.payment_token,
+            &env.current_contract_address(),
+        )?;
+
+        env.events().publish(
+            (symbol_short!("auction"), symbol_short!("settled"), auction_id),
+            receipt.clone(),
+        );
+        Ok(Some(receipt))
+    }
+
+    // El vendedor puede retirar una subasta mientras no tenga pujas.
+    pub fn cancel_auction(env: Env, seller: Address, auction_id: u64) -> Result<(), Error> {
+        Self::extend_instance_ttl(&env);
+        seller.require_auth();
+        let auction = Self::get_auction(env.clone(), auction_id)?;
+        if auction.seller != seller {
+            return Err(MarketplaceError::Unauthorized.into());
+        }
+        if auction.highest_bidder.is_some() {
+            return Err(AuctionError::InvalidAuction.into());
+        }
+        Self::close_auction(&env, &auction)?;
+        env.events().publish(
+            (symbol_short!("auction"), symbol_short!("cancel"), auction_id),
+            (),
+        );
+        Ok(())
+    }
+
+    pub fn get_auction(env: Env, auction_id: u64) -> Result<Auction, Error> {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::Auction(auction_id))
+            .ok_or(AuctionError::AuctionNotFound.into())
+    }
+
+    pub fn get_auction_price(env: Env, auction_id: u64) -> Result<i128, Error> {
+        Self::extend_instance_ttl(&env);
+        let auction = Self::get_auction(env.clone(), auction_id)?;
+        Ok(Self::auction_price(&env, &auction)?)
+    }
+
+    pub fn get_auction_reserved(env: Env, asset_id: u64) -> i128 {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::AuctionReserved(asset_id)).unwrap_or(0)
+    }
+
+    pub fn list_auctions(env: Env, from_id: u64, limit: u32) -> Map<u64, Auction> {
+        Self::extend_instance_ttl(&env);
+        let mut out: Map<u64, Auction> = Map::new(&env);
+        let max = if limit > Self::MAX_PAGE_SIZE { Self::MAX_PAGE_SIZE } else { limit };
+        let next_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextAuctionId)
+            .unwrap_or(1);
+
+        let mut current = from_id;
+        let mut count: u32 = 0;
+        while current < next_id && count < max {
+            if let Some(auction) = Self::read_persistent::<Auction>(&env, &DataKey::Auction(current)) {
+                out.set(current, auction);
+                count += 1;
+            }
+            current += 1;
+        }
+
+        out
+    }
+
+    pub fn deposit_payout(
+        env: Env,
+        seller: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        seller.require_auth();
+        if amount <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+        Self::ensure_not_paused(&env, asset_id)?;
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&asset_id);
+        if asset.seller != seller {
+            return Err(MarketplaceError::Unauthorized);
+        }
+        if asset.lifecycle_status != LifecycleStatus::Settled {
+            return Err(MarketplaceError::AssetNotSettled);
+        }
+
+        let holders_supply = asset.circulating_supply;
+        if holders_supply <= 0 {
+            return Err(MarketplaceError::NoTokensInCirculation);
+        }
+
+        let mut pool = match Self::get_payout_pool(env.clone(), asset_id) {
+            Some(pool) => pool,
+            None => PayoutPool {
+                token: Self::get_payment_token(env.clone())?,
+                payout_per_token: 0,
+                total_deposited: 0,
+                total_claimed: 0,
+            },
+        };
+
+        token::Client::new(&env, &pool.token).transfer(
+            &seller,
+            &env.current_contract_address(),
+            &amount,
+        );
+
+        let increment = amount
+            .checked_mul(Self::PAYOUT_SCALE)
+            .ok_or(MarketplaceError::Overflow)?
+            / holders_supply;
+        pool.payout_per_token = pool
+            .payout_per_token
+            .checked_add(increment)
+            .ok_or(MarketplaceError::Overflow)?;
+        pool.total_deposited = pool
+            .total_deposited
+            .checked_add(amount)
+            .ok_or(MarketplaceError::Overflow)?;
+        Self::write_persistent(&env, &DataKey::PayoutPool(asset_id), &pool);
+        env.events().publish(
+            (symbol_short!("payout"), symbol_short!("deposit"), asset_id),
+            (seller, amount, pool.payout_per_token),
+        );
+        Ok(())
+    }
+
+    pub fn claim_payout(env: Env, holder: Address, asset_id: u64) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        holder.require_auth();
+        let amount = Self::settle_payout(env.clone(), holder, asset_id)?;
+        if amount == 0 {
+            return Err(MarketplaceError::NothingToClaim);
+        }
+        Ok(amount)
+    }
+
+    // Canjear es definitivo: los depositos posteriores se reparten solo entre los
+    // tokens que siguen en circulacion, asi que los tokens canjeados no cobran nada
+    // de lo que el vendedor deposite despues.
+    pub fn redeem(
+        env: Env,
+        holder: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        holder.require_auth();
+        if amount <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+
+        // Antes de quemar se paga todo lo pendiente del holder: despues del canje
+        // su balance ya no cuenta para el acumulador. Sin payout depositado (perdida
+        // total) no hay nada pendiente, pero el canje sigue permitido.
+        Self::ensure_not_paused(&env, asset_id)?;
+        let paid = if Self::get_payout_pool(env.clone(), asset_id).is_some() {
+            Self::settle_payout(env.clone(), holder.clone(), asset_id)?
+        } else {
+            0
+        };
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        Self::authorize_tokenization_call(
+            env.clone(),
+            tokenization.clone(),
+            "redeem",
+            vec![
+                &env,
+                holder.into_val(&env),
+                asset_id.into_val(&env),
+                amount.into_val(&env),
+            ],
+        );
+        TokenizationClient::new(&env, &tokenization).redeem(&holder, &asset_id, &amount);
+        env.events()
+            .publish((symbol_short!("redeem"), asset_id, holder), (amount, paid));
+
+        Ok(paid)
+    }
+
+    pub fn get_pending_payout(env: Env, asset_id: u64, holder: Address) -> Result<i128, MarketplaceError> {
+        Self::extend_instance_ttl(&env);
+        let Some(pool) = Self::get_payout_pool(env.clone(), asset_id) else {
+            return Ok(0);
+        };
+        let position = Self::get_payout_position(env.clone(), asset_id, holder.clone());
+        Self::pending_payout(env, asset_id, holder, &pool, &position)
+    }
+
+    pub fn get_payout_pool(env: Env, asset_id: u64) -> Option<PayoutPool> {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::PayoutPool(asset_id))
+    }
+
+    pub fn get_payout_position(env: Env, asset_id: u64, holder: Address) -> PayoutPosition {
+        Self::extend_instance_ttl(&env);
+        Self::read_persistent(&env, &DataKey::PayoutPosition((asset_id, holder)))
+            .unwrap_or(PayoutPosition {
+                checkpoint: 0,
+                claimed: 0,
+            })
+    }
+
+    fn purchase(
+        env: Env,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+        max_total_paid: Option<i128>,
+        referrer: Option<Address>,
+        pay_with: Address,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        if quantity <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(&env, &tokenization).get_asset(&asset_id);
+        let receipt = Self::quote_purchase(
+            env.clone(),
+            &asset,
+            buyer.clone(),
+            quantity,
+            asset.price_per_token,
+            referrer,
+            &Self::get_payment_token(env.clone())?,
+        )?;
+        let receipt = Self::convert_receipt(&env, receipt, &pay_with)?;
+        if let Some(max_total_paid) = max_total_paid {
+            if receipt.total_paid > max_total_paid {
+                return Err(MarketplaceError::SlippageExceeded);
+            }
+        }
+        Self::complete_primary_sale(&env, &tokenization, &asset, &receipt, &pay_with, &buyer)?;
+
+        env.events()
+            .publish((symbol_short!("sale"), asset_id, buyer), receipt.clone());
+        Ok(receipt)
+    }
+
+    // Cobra y ejecuta una venta primaria ya cotizada. `payer` es el comprador o el
+    // propio marketplace cuando los fondos ya estaban bloqueados (ofertas, subastas).
+    fn complete_primary_sale(
+        env: &Env,
+        tokenization: &Address,
+        asset: &Asset,
+        receipt: &PurchaseReceipt,
+        payment_token: &Address,
+        payer: &Address,
+    ) -> Result<(), MarketplaceError> {
+        let contract = env.current_contract_address();
+        if Self::hold_funding_payment(env, asset, payment_token, receipt)? {
+            if *payer != contract {
+                token::Client::new(env, payment_token).transfer(payer, &contract, &receipt.total_paid);
+            }
+        } else {
+            let mut legs: Map<Address, i128> = Map::new(env);
+            Self::add_payment_legs(
+                env,
+                &mut legs,
+                &asset.seller,
+                receipt.fee_paid,
+                receipt.seller_amount,
+                &Self::receipt_referrals(env, receipt),
+            )?;
+            Self::pay_legs(env, payment_token, payer, &legs)?;
+            if let Some(referrer) = receipt.referrer.clone() {
+                let amount = receipt.referral_amount;
+                Self::record_referral(env, asset.id, &referrer, payment_token, amount)?;
+            }
+        }
+        Self::record_buyer_volume(env, &receipt.buyer, receipt.quoted_total)?;
+
+        Self::authorize_tokenization_call(
+            env.clone(),
+            tokenization.clone(),
+            "execute_sale",
+            vec![
+                env,
+                asset.seller.into_val(env),
+                receipt.buyer.into_val(env),
+                asset.id.into_val(env),
+                receipt.quantity.into_val(env),
+            ],
+        );
+        let _ = TokenizationClient::new(env, tokenization).execute_sale(
+            &asset.seller,
+            &receipt.buyer,
+            &asset.id,
+            &receipt.quantity,
+        );
+        Ok(())
+    }
+
+    fn quote_purchase(
+        env: Env,
+        asset: &Asset,
+        buyer: Address,
+        quantity: i128,
+        price_per_token: i128,
+        referrer: Option<Address>,
+        payment_token: &Address,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        if quantity <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+        Self::ensure_not_paused(&env, asset.id)?;
+        if !asset.active {
+            return Err(MarketplaceError::AssetNotActive);
+        }
+        if asset.lifecycle_status != LifecycleStatus::Funding {
+            return Err(MarketplaceError::AssetNotInFunding);
+        }
+        // Los tokens reservados por subastas abiertas no se venden por otra via.
+        let unreserved = asset
+            .available_tokens
+            .checked_sub(Self::get_auction_reserved(env.clone(), asset.id))
+            .ok_or(MarketplaceError::Overflow)?;
+        if unreserved < quantity {
+            return Err(MarketplaceError::InsufficientAvailableTokens);
+        }
+        if asset.funding_deadline > 0 {
+            match Self::get_funding_pool(env.clone(), asset.id).map(|pool| pool.status) {
+                Some(FundingStatus::Failed) => return Err(MarketplaceError::FundingFailed),
+                Some(FundingStatus::Succeeded) => {}
+                Some(FundingStatus::Open) | None => {
+                    if env.ledger().timestamp() >= asset.funding_deadline {
+                        return Err(MarketplaceError::FundingClosed);
+                    }
+                }
+            }
+        }
+        // Tokenizacion vuelve a validarlo en `execute_sale`; aca se corta antes de
+        // mover pagos y para que `preview_purchase` muestre el motivo.
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let tokenization_client = TokenizationClient::new(&env, &tokenization);
+        if !tokenization_client.is_eligible(&asset.id, &buyer) {
+            return Err(MarketplaceError::BuyerNotVerified);
+        }
+        if quantity < asset.min_purchase {
+            return Err(MarketplaceError::BelowMinPurchase);
+        }
+        if let Some(limit) = asset.max_per_wallet.limit(asset.total_tokens) {
+            let held = tokenization_client
+                .get_buyer_balance(&asset.id, &buyer)
+                .checked_add(tokenization_client.get_escrowed_balance(&asset.id, &buyer))
+                .and_then(|held| held.checked_add(quantity))
+                .ok_or(MarketplaceError::Overflow)?;
+            if held > limit {
+                return Err(MarketplaceError::WalletCapExceeded);
+            }
+        }
+
+        let total = price_per_token
+            .checked_mul(quantity)
+            .ok_or(MarketplaceError::Overflow)?;
+        let fee_bps = Self::resolve_fee_bps(&env, asset.id, &asset.seller, &buyer);
+        let (fee, seller_amount) = Self::split_total(total, fee_bps)?;
+        if referrer.as_ref() == Some(&buyer) {
+            return Err(MarketplaceError::InvalidReferrer);
+        }
+        let referral_amount = if referrer.is_some() {
+            Self::calc_bps(fee, Self::get_referral_share_bps(env.clone()))?
+        } else {
+            0
+        };
+
+        Ok(PurchaseReceipt {
+            asset_id: asset.id,
+            seller: asset.seller.clone(),
+            buyer,
+            quantity,
+            total_paid: total,
+            fee_paid: fee,
+            seller_amount,
+            referrer,
+            referral_amount,
+            payment_token: payment_token.clone(),
+            quoted_total: total,
+        })
+    }
+
+    // Pasa un recibo cotizado en el token base a `pay_with`. El total se redondea
+    // hacia arriba y fee y referido se reparten en la misma proporcion.
+    fn convert_receipt(
+        env: &Env,
+        mut receipt: PurchaseReceipt,
+        pay_with: &Address,
+    ) -> Result<PurchaseReceipt, MarketplaceError> {
+        if receipt.payment_token == *pay_with {
+            return Ok(receipt);
+        }
+        let rate = Self::conversion_rate(env, pay_with)?;
+        let total = receipt
+            .quoted_total
+            .checked_mul(rate)
+            .and_then(|value| value.checked_add(Self::RATE_SCALE - 1))
+            .ok_or(MarketplaceError::Overflow)?
+            / Self::RATE_SCALE;
+        let fee = if receipt.quoted_total > 0 {
+            total
+                .checked_mul(receipt.fee_paid)
+                .ok_or(MarketplaceError::Overflow)?
+                / receipt.quoted_total
+        } else {
+            0
+        };
+        let referral_amount = if receipt.fee_paid > 0 {
+            fee.checked_mul(receipt.referral_amount)
+                .ok_or(MarketplaceError::Overflow)?
+                / receipt.fee_paid
+        } else {
+            0
+        };
+
+        receipt.total_paid = total;
+        receipt.fee_paid = fee;
+        receipt.seller_amount = total - fee;
+        receipt.referral_amount = referral_amount;
+        receipt.payment_token = pay_with.clone();
+        Ok(receipt)
+    }
+
+    fn conversion_rate(env: &Env, token: &Address) -> Result<i128, MarketplaceError> {
+        let config = Self::get_payment_rates(env.clone())
+            .get(token.clone())
+            .ok_or(MarketplaceError::UnsupportedPaymentToken)?;
+        let (rate, updated_at) = match config.source {
+            RateSource::Manual => (config.rate, config.updated_at),
+            RateSource::Oracle(oracle, feed) => {
+                let price = OracleClient::new(env, &oracle).get_price(&feed);
+                (price.value, price.timestamp)
+            }
+        };
+        if rate <= 0 {
+            return Err(MarketplaceError::InvalidRate);
+        }
+        if env.ledger().timestamp() > updated_at.saturating_add(config.max_age) {
+            return Err(MarketplaceError::StalePrice);
+        }
+        Ok(rate)
+    }
+
+    fn write_payment_rate(env: &Env, token: &Address, rate: PaymentRate) {
+        let mut rates = Self::get_payment_rates(env.clone());
+        rates.set(token.clone(), rate);
+        env.storage().instance().set(&DataKey::PaymentRates, &rates);
+    }
+
+    // Si el activo esta en una ronda con escrow abierta, registra el pago como
+    // retenido (el llamador lo deja en el contrato) y devuelve `true`; si no,
+    // el llamador lo reparte directo.
+    fn hold_funding_payment(
+        env: &Env,
+        asset: &Asset,
+        payment_token: &Address,
+        receipt: &PurchaseReceipt,
+    ) -> Result<bool, MarketplaceError> {
+        if asset.funding_deadline == 0 {
+            return Ok(false);
+        }
+        let key = DataKey::FundingPool(asset.id);
+        let base_token = Self::get_payment_token(env.clone())?;
+        let mut pool = Self::read_persistent::<FundingPool>(env, &key).unwrap_or(FundingPool {
+            payment_token: base_token,
+            seller_amount: 0,
+            fee_amount: 0,
+            referrals: Map::new(env),
+            status: FundingStatus::Open,
+        });
+        if pool.status == FundingStatus::Succeeded {
+            return Ok(false);
+        }
+        if pool.status == FundingStatus::Failed {
+            return Err(MarketplaceError::FundingFailed);
+        }
+        if pool.payment_token != *payment_token {
+            return Err(MarketplaceError::PaymentTokenMismatch);
+        }
+
+        pool.seller_amount = pool
+            .seller_amount
+            .checked_add(receipt.seller_amount)
+            .ok_or(MarketplaceError::Overflow)?;
+        pool.fee_amount = pool
+            .fee_amount
+            .checked_add(receipt.fee_paid)
+            .ok_or(MarketplaceError::Overflow)?;
+        if let Some(referrer) = receipt.referrer.clone() {
+            Self::add_leg(&mut pool.referrals, &referrer, receipt.referral_amount)?;
+        }
+        Self::write_persistent(env, &key, &pool);
+
+        let contribution_key = DataKey::Contribution((asset.id, receipt.buyer.clone()));
+        let mut contribution = Self::read_persistent::<Contribution>(env, &contribution_key)
+            .unwrap_or(Contribution { quantity: 0, paid: 0, quoted_total: 0 });
+        contribution.quantity = contribution
+            .quantity
+            .checked_add(receipt.quantity)
+            .ok_or(MarketplaceError::Overflow)?;
+        contribution.paid = contribution
+            .paid
+            .checked_add(receipt.total_paid)
+            .ok_or(MarketplaceError::Overflow)?;
+        contribution.quoted_total = contribution
+            .quoted_total
+            .checked_add(receipt.quoted_total)
+            .ok_or(MarketplaceError::Overflow)?;
+        Self::write_persistent(env, &contribution_key, &contribution);
+        Ok(true)
+    }
+
+    // Una ronda abierta solo cobra en su token, que es el token base vigente al
+    // abrirse; el resto de los casos paga directo o falla en otro lado.
+    fn ensure_funding_token(
+        env: &Env,
+        asset: &Asset,
+        payment_token: &Address,
+    ) -> Result<(), MarketplaceError> {
+        if asset.funding_deadline == 0 {
+            return Ok(());
+        }
+        let pool = Self::read_persistent::<FundingPool>(env, &DataKey::FundingPool(asset.id));
+        let round_token = match pool {
+            Some(pool) if pool.status != FundingStatus::Open => return Ok(()),
+            Some(pool) => pool.payment_token,
+            None => Self::get_payment_token(env.clone())?,
+        };
+        if round_token != *payment_token {
+            return Err(MarketplaceError::PaymentTokenMismatch);
+        }
+        Ok(())
+    }
+
+    fn resolve_fee_bps(env: &Env, asset_id: u64, seller: &Address, buyer: &Address) -> i128 {
+        let base = Self::read_persistent::<i128>(env, &DataKey::AssetFeeBps(asset_id))
+            .or_else(|| Self::read_persistent::<i128>(env, &DataKey::SellerFeeBps(seller.clone())))
+            .unwrap_or_else(|| Self::get_fee_bps(env.clone()));
+
+        let volume: i128 = Self::read_persistent(env, &DataKey::BuyerVolume(buyer.clone())).unwrap_or(0);
+        let mut tier_bps: Option<i128> = None;
+        for tier in Self::get_fee_tiers(env.clone()).iter() {
+            if volume >= tier.min_volume {
+                tier_bps = Some(tier.fee_bps);
+            }
+        }
+        match tier_bps {
+            Some(bps) if bps < base => bps,
+            _ => base,
+        }
+    }
+
+    fn record_buyer_volume(env: &Env, buyer: &Address, amount: i128) -> Result<(), MarketplaceError> {
+        let key = DataKey::BuyerVolume(buyer.clone());
+        let volume = Self::read_persistent::<i128>(env, &key)
+            .unwrap_or(0)
+            .checked_add(amount)
+            .ok_or(MarketplaceError::Overflow)?;
+        if volume <= 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            Self::write_persistent(env, &key, &volume);
+        }
+        Ok(())
+    }
+
+    fn validate_fee_bps(fee_bps: i128) -> Result<(), MarketplaceError> {
+        if !(0..=Self::MAX_FEE_BPS).contains(&fee_bps) {
+            return Err(MarketplaceError::InvalidFeeBps);
+        }
+        Ok(())
+    }
+
+    fn split_total(total: i128, fee_bps: i128) -> Result<(i128, i128), MarketplaceError> {
+        let fee = Self::calc_bps(total, fee_bps)?;
+        let seller_amount = total.checked_sub(fee).ok_or(MarketplaceError::Overflow)?;
+        Ok((fee, seller_amount))
+    }
+
+    // Reparte un pago: neto al vendedor y fee entre tesoreria y liquidez.
+    fn distribute_payment(
+        env: &Env,
+        payment_token: &Address,
+        payer: &Address,
+        seller: &Address,
+        fee: i128,
+        seller_amount: i128,
+    ) -> Result<(), MarketplaceError> {
+        let mut legs: Map<Address, i128> = Map::new(env);
+        Self::add_payment_legs(env, &mut legs, seller, fee, seller_amount, &Map::new(env))?;
+        Self::pay_legs(env, payment_token, payer, &legs)
+    }
+
+    // Acumula por destinatario lo que le toca de un pago, para que varias compras
+    // se liquiden con una sola transferencia por direccion. `referrals` ya esta
+    // incluido en `fee`; tesoreria recibe lo que queda despues de liquidez y referidos.
+    fn add_payment_legs(
+        env: &Env,
+        legs: &mut Map<Address, i128>,
+        seller: &Address,
+        fee: i128,
+        seller_amount: i128,
+        referrals: &Map<Address, i128>,
+    ) -> Result<(), MarketplaceError> {
+        Self::add_leg(legs, seller, seller_amount)?;
+
+        if fee > 0 {
+            let treasury = Self::get_treasury(env.clone())?;
+            let mut treasury_amount = fee;
+            for (referrer, amount) in referrals.iter() {
+                treasury_amount = treasury_amount
+                    .checked_sub(amount)
+                    .ok_or(MarketplaceError::Overflow)?;
+                Self::add_leg(legs, &referrer, amount)?;
+            }
+
+            let liquidity_destination = env
+                .storage()
+                .instance()
+                .get::<DataKey, Address>(&DataKey::LiquidityDestination);
+            if let Some(destination) = liquidity_destination {
+                let liquidity_share_bps = Self::get_liquidity_share_bps(env.clone());
+                // Tope por si la porcion de referidos retenida en un escrow se calculo
+                // con una configuracion anterior.
+                let liquidity_amount = Self::calc_bps(fee, liquidity_share_bps)?.min(treasury_amount);
+                treasury_amount = treasury_amount
+                    .checked_sub(liquidity_amount)
+                    .ok_or(MarketplaceError::Overflow)?;
+                Self::add_leg(legs, &destination, liquidity_amount)?;
+            }
+            Self::add_leg(legs, &treasury, treasury_amount)?;
+        }
+        Ok(())
+    }
+
+    fn receipt_referrals(env: &Env, receipt: &PurchaseReceipt) -> Map<Address, i128> {
+        let mut referrals: Map<Address, i128> = Map::new(env);
+        if let Some(referrer) = receipt.referrer.clone() {
+            if receipt.referral_amount > 0 {
+                referrals.set(referrer, receipt.referral_amount);
+            }
+        }
+        referrals
+    }
+
+    fn record_referral(
+        env: &Env,
+        asset_id: u64,
+        referrer: &Address,
+        token: &Address,
+        amount: i128,
+    ) -> Result<(), MarketplaceError> {
+        if amount <= 0 {
+            return Ok(());
+        }
+        let key = DataKey::ReferralTotal((referrer.clone(), token.clone()));
+        let total = Self::read_persistent::<i128>(env, &key)
+            .unwrap_or(0)
+            .checked_add(amount)
+            .ok_or(MarketplaceError::Overflow)?;
+        Self::write_persistent(env, &key, &total);
+        env.events().publish(
+            (symbol_short!("referral"), referrer.clone()),
+            (asset_id, token.clone(), amount),
+        );
+        Ok(())
+    }
+
+    fn add_leg(legs: &mut Map<Address, i128>, to: &Address, amount: i128) -> Result<(), MarketplaceError> {
+        if amount <= 0 {
+            return Ok(());
+        }
+        let current = legs.get(to.clone()).unwrap_or(0);
+        legs.set(
+            to.clone(),
+            current.checked_add(amount).ok_or(MarketplaceError::Overflow)?,
+        );
+        Ok(())
+    }
+
+    // En modo acumulacion el pagador hace una sola transferencia al marketplace
+    // (ninguna si ya es el marketplace) y cada destinatario queda con saldo
+    // interno para `withdraw`; si no, se transfiere a cada uno.
+    fn pay_legs(
+        env: &Env,
+        payment_token: &Address,
+        payer: &Address,
+        legs: &Map<Address, i128>,
+    ) -> Result<(), MarketplaceError> {
+        let token_client = token::Client::new(env, payment_token);
+        if !Self::is_accrual_mode(env.clone()) {
+            for (to, amount) in legs.iter() {
+                token_client.transfer(payer, &to, &amount);
+            }
+            return Ok(());
+        }
+
+        let contract = env.current_contract_address();
+        let mut total: i128 = 0;
+        for (to, amount) in legs.iter() {
+            total = total.checked_add(amount).ok_or(MarketplaceError::Overflow)?;
+            // El tramo de escrow de una ronda ya queda en el contrato; no es saldo
+            // retirable de nadie.
+            if to != contract {
+                Self::credit_accrued(env, &to, payment_token, amount)?;
+            }
+        }
+        if total > 0 && *payer != contract {
+            token_client.transfer(payer, &contract, &total);
+        }
+        Ok(())
+    }
+
+    fn close_auction(env: &Env, auction: &Auction) -> Result<(), MarketplaceError> {
+        env.storage().persistent().remove(&DataKey::Auction(auction.id));
+        Self::add_auction_reserved(env, auction.asset_id, -auction.quantity)
+    }
+
+    fn credit_accrued(env: &Env, to: &Address, token: &Address, amount: i128) -> Result<(), MarketplaceError> {
+        let key = DataKey::Accrued((to.clone(), token.clone()));
+        let accrued = Self::read_persistent::<i128>(env, &key)
+            .unwrap_or(0)
+            .checked_add(amount)
+            .ok_or(MarketplaceError::Overflow)?;
+        Self::write_persistent(env, &key, &accrued);
+        env.events()
+            .publish((symbol_short!("accrue"), to.clone(), token.clone()), amount);
+        Ok(())
+    }
+
+    fn add_auction_reserved(env: &Env, asset_id: u64, delta: i128) -> Result<(), MarketplaceError> {
+        let key = DataKey::AuctionReserved(asset_id);
+        let reserved = Self::get_auction_reserved(env.clone(), asset_id)
+            .checked_add(delta)
+            .ok_or(MarketplaceError::Overflow)?;
+        if reserved <= 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            Self::write_persistent(env, &key, &reserved);
+        }
+        Ok(())
+    }
+
+    // Precio vigente: en una holandesa baja linealmente de `start_price` a
+    // `reserve_price` entre `start_at` y `end_at`; en una inglesa es la puja
+    // mas alta o, sin pujas, la reserva.
+    fn auction_price(env: &Env, auction: &Auction) -> Result<i128, MarketplaceError> {
+        match auction.kind {
+            AuctionKind::English => Ok(if auction.highest_bidder.is_some() {
+                auction.highest_bid
+            } else {
+                auction.reserve_price
+            }),
+            AuctionKind::Dutch => {
+                let now = env.ledger().timestamp();
+                if now >= auction.end_at {
+                    return Ok(auction.reserve_price);
+                }
+                let elapsed = i128::from(now.saturating_sub(auction.start_at));
+                let duration = i128::from(auction.end_at - auction.start_at);
+                let drop = auction
+                    .start_price
+                    .checked_sub(auction.reserve_price)
+                    .and_then(|range| range.checked_mul(elapsed))
+                    .ok_or(MarketplaceError::Overflow)?
+                    / duration;
+                auction
+                    .start_price
+                    .checked_sub(drop)
+                    .ok_or(MarketplaceError::Overflow)
+            }
+        }
+    }
+
+    // Valida la subasta armada por `create_*_auction`, reserva los tokens y le
+    // asigna id.
+    fn open_auction(env: &Env, mut auction: Auction) -> Result<u64, MarketplaceError> {
+        let asset_id = auction.asset_id;
+        let quantity = auction.quantity;
+        if auction.end_at <= auction.start_at {
+            return Err(MarketplaceError::InvalidExpiry);
+        }
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let asset = TokenizationClient::new(env, &tokenization).get_asset(&asset_id);
+        if asset.seller != auction.seller {
+            return Err(MarketplaceError::Unauthorized);
+        }
+        if quantity <= 0 {
+            return Err(MarketplaceError::InvalidAmount);
+        }
+        Self::ensure_not_paused(env, asset_id)?;
+        if !asset.active {
+            return Err(MarketplaceError::AssetNotActive);
+        }
+        if asset.lifecycle_status != LifecycleStatus::Funding {
+            return Err(MarketplaceError::AssetNotInFunding);
+        }
+        let unreserved = asset
+            .available_tokens
+            .checked_sub(Self::get_auction_reserved(env.clone(), asset_id))
+            .ok_or(MarketplaceError::Overflow)?;
+        if unreserved < quantity {
+            return Err(MarketplaceError::InsufficientAvailableTokens);
+        }
+        Self::add_auction_reserved(env, asset_id, quantity)?;
+
+        let auction_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextAuctionId)
+            .unwrap_or(1);
+        env.storage().instance().set(&DataKey::NextAuctionId, &(auction_id + 1));
+
+        auction.id = auction_id;
+        Self::write_persistent(env, &DataKey::Auction(auction_id), &auction);
+        env.events().publish(
+            (symbol_short!("auction"), symbol_short!("create"), asset_id),
+            auction,
+        );
+        Ok(auction_id)
+    }
+
+    // Paga lo pendiente del holder y mueve su checkpoint al acumulado actual,
+    // de modo que el mismo deposito no se puede reclamar dos veces.
+    fn settle_payout(env: Env, holder: Address, asset_id: u64) -> Result<i128, MarketplaceError> {
+        Self::ensure_not_paused(&env, asset_id)?;
+        let mut pool = Self::get_payout_pool(env.clone(), asset_id)
+            .ok_or(MarketplaceError::NoPayoutDeposited)?;
+        let mut position = Self::get_payout_position(env.clone(), asset_id, holder.clone());
+        let amount = Self::pending_payout(env.clone(), asset_id, holder.clone(), &pool, &position)?;
+
+        position.checkpoint = pool.payout_per_token;
+        position.claimed = position
+            .claimed
+            .checked_add(amount)
+            .ok_or(MarketplaceError::Overflow)?;
+        Self::write_persistent(&env, &DataKey::PayoutPosition((asset_id, holder.clone())), &position);
+
+        if amount > 0 {
+            pool.total_claimed = pool
+                .total_claimed
+                .checked_add(amount)
+                .ok_or(MarketplaceError::Overflow)?;
+            Self::write_persistent(&env, &DataKey::PayoutPool(asset_id), &pool);
+            token::Client::new(&env, &pool.token).transfer(
+                &env.current_contract_address(),
+                &holder,
+                &amount,
+            );
+            env.events().publish(
+                (symbol_short!("payout"), symbol_short!("claim"), asset_id),
+                (holder, amount),
+            );
+        }
+
+        Ok(amount)
+    }
+
+    fn pending_payout(
+        env: Env,
+        asset_id: u64,
+        holder: Address,
+        pool: &PayoutPool,
+        position: &PayoutPosition,
+    ) -> Result<i128, MarketplaceError> {
+        let tokenization = Self::get_tokenization_contract(env.clone())?;
+        let tokenization_client = TokenizationClient::new(&env, &tokenization);
+        // Los tokens en escrow por una orden de venta siguen siendo del holder.
+        let balance = tokenization_client
+            .get_buyer_balance(&asset_id, &holder)
+            .checked_add(tokenization_client.get_escrowed_balance(&asset_id, &holder))
+            .ok_or(MarketplaceError::Overflow)?;
+        let delta = pool
+            .payout_per_token
+            .checked_sub(position.checkpoint)
+            .ok_or(MarketplaceError::Overflow)?;
+        Ok(balance.checked_mul(delta).ok_or(MarketplaceError::Overflow)? / Self::PAYOUT_SCALE)
+    }
+
+    fn ensure_not_paused(env: &Env, asset_id: u64) -> Result<(), MarketplaceError> {
+        if Self::is_paused(env.clone()) || Self::is_asset_paused(env.clone(), asset_id) {
+            return Err(MarketplaceError::Paused);
+        }
+        Ok(())
+    }
+
+    fn extend_instance_ttl(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(Self::INSTANCE_LIFETIME_THRESHOLD, Self::INSTANCE_BUMP_AMOUNT);
+    }
+
+    // Toda lectura/escritura persistente renueva el TTL de la entrada para que
+    // no se archive mientras el activo siga en uso.
+    fn read_persistent<V: TryFromVal<Env, Val>>(env: &Env, key: &DataKey) -> Option<V> {
+        let value = env.storage().persistent().get::<DataKey, V>(key);
+        if value.is_some() {
+            env.storage().persistent().extend_ttl(
+                key,
+                Self::PERSISTENT_LIFETIME_THRESHOLD,
+                Self::PERSISTENT_BUMP_AMOUNT,
+            );
+        }
+        value
+    }
+
+    fn write_persistent<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(
+            key,
+            Self::PERSISTENT_LIFETIME_THRESHOLD,
+            Self::PERSISTENT_BUMP_AMOUNT,
+        );
+    }
+
+    fn authorize_tokenization_call(
+        env: Env,
+        tokenization_contract: Address,
+        fn_name: &str,
+        args: Vec<Val>,
+    ) {
+        let context = ContractContext {
+            contract: tokenization_contract,
+            fn_name: Symbol::new(&env, fn_name),
+            args,
+        };
+        let entry = InvokerContractAuthEntry::Contract(SubContractInvocation {
+            context,
+            sub_invocations: Vec::new(&env),
+        });
+        env.authorize_as_current_contract(vec![&env, entry]);
+    }
+
+    fn require_role(env: Env, role: Role) -> Result<(), MarketplaceError> {
+        let holder = match Self::get_role(env.clone(), role) {
+            Some(holder) => holder,
+            None => Self::get_admin(env)?,
+        };
+        holder.require_auth();
+        Ok(())
+    }
+
+    fn get_tokenization_contract(env: Env) -> Result<Address, MarketplaceError> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::TokenizationContract)
+            .ok_or(MarketplaceError::TokenizationContractMissing)
+    }
+
+    fn get_payment_token(env: Env) -> Result<Address, MarketplaceError> {
+        if let Some(active_network) = env.storage().instance().get::<DataKey, Symbol>(&DataKey::ActiveNetwork) {
+            if let Some(token) = env
+                .storage()
+                .instance()
+                .get::<DataKey, Address>(&DataKey::PaymentTokenByNetwork(active_network))
+            {
+                return Ok(token);
+            }
+        }
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::PaymentToken)
+            .ok_or(MarketplaceError::PaymentTokenMissing)
+    }
+
+    fn get_treasury(env: Env) -> Result<Address, MarketplaceError> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::Treasury)
+            .ok_or(MarketplaceError::TreasuryMissing)
+    }
+
+    fn get_fee_bps(env: Env) -> i128 {
+        env.storage()
+            .instance()
+            .get::<DataKey, i128>(&DataKey::FeeBps)
+            .unwrap_or(0)
+    }
+
+    fn get_liquidity_share_bps(env: Env) -> i128 {
+        env.storage()
+            .instance()
+            .get::<DataKey, i128>(&DataKey::LiquidityShareBps)
+            .unwrap_or(0)
+    }
+
+    fn calc_bps(amount: i128, bps: i128) -> Result<i128, MarketplaceError> {
+        Ok(amount.checked_mul(bps).ok_or(MarketplaceError::Overflow)? / Self::BPS_DENOMINATOR)
+    }
+
+    fn is_supported_network(env: Env, network: Symbol) -> bool {
+        network == Symbol::new(&env, "testnet") || network == Symbol::new(&env, "mainnet")
+    }
+}
+
+mod test;
@@ -0,0 +1,881 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Events, Ledger, MockAuth, MockAuthInvoke};
+use soroban_sdk::token::{StellarAssetClient, TokenClient};
+use terra_tokenization::{TerraTokenization, TerraTokenizationClient, TokenizationError};
+
+const DAY: u64 = 86_400;
+
+// Marketplace con tokenizacion y token de pago reales; fee global de 300 bps y
+// un activo de 1000 tokens a 100.
+struct Market<'a> {
+    env: Env,
+    client: TerraMarketplaceClient<'a>,
+    tokenization: TerraTokenizationClient<'a>,
+    token: Address,
+    treasury: Address,
+    seller: Address,
+    asset_id: u64,
+}
+
+fn setup_market(env: &Env) -> Market<'_> {
+    env.mock_all_auths();
+
+    let admin = Address::generate(env);
+    let tokenization = TerraTokenizationClient::new(env, &env.register(TerraTokenization, ()));
+    tokenization.init(&admin);
+    let token = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    let treasury = Address::generate(env);
+    let client = TerraMarketplaceClient::new(env, &env.register(TerraMarketplace, ()));
+    client.init(&admin, &tokenization.address, &token, &treasury, &300);
+    tokenization.set_marketplace(&client.address);
+
+    let seller = Address::generate(env);
+    let asset_id = tokenization.create_asset(
+        &seller,
+        &String::from_str(env, "cultivo"),
+        &String::from_str(env, "Soja lote 4"),
+        &100,
+        &1_000,
+        &30,
+        &None,
+    );
+    Market {
+        env: env.clone(),
+        client,
+        tokenization,
+        token,
+        treasury,
+        seller,
+        asset_id,
+    }
+}
+
+impl Market<'_> {
+    fn funded(&self, amount: i128) -> Address {
+        let account = Address::generate(&self.env);
+        StellarAssetClient::new(&self.env, &self.token).mint(&account, &amount);
+        account
+    }
+
+    fn new_asset(&self, seller: &Address) -> u64 {
+        self.tokenization.create_asset(
+            seller,
+            &String::from_str(&self.env, "tierra"),
+            &String::from_str(&self.env, "Campo norte"),
+            &100,
+            &1_000,
+            &60,
+            &None,
+        )
+    }
+
+    fn balance(&self, account: &Address) -> i128 {
+        TokenClient::new(&self.env, &self.token).balance(account)
+    }
+
+    fn buy(&self, buyer: &Address, quantity: i128) -> PurchaseReceipt {
+        self.client
+            .buy_tokens(buyer, &self.asset_id, &quantity, &None, &self.token)
+    }
+
+    fn advance(&self, seconds: u64) {
+        self.env.ledger().with_mut(|ledger| ledger.timestamp += seconds);
+    }
+
+    // FUNDING -> OPERATING -> SETTLED con el ciclo de 30 dias cumplido.
+    fn settle(&self) {
+        self.tokenization.start_operation(&self.seller, &self.asset_id);
+        self.advance(30 * DAY);
+        self.tokenization.settle_asset(&self.seller, &self.asset_id);
+    }
+}
+
+#[test]
+fn instance_config_survives_while_in_use() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(TerraMarketplace, ());
+    let client = TerraMarketplaceClient::new(&env, &contract_id);
+    let admin = Address::generate(&env);
+    let tokenization = Address::generate(&env);
+    let payment_token = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    client.init(&admin, &tokenization, &payment_token, &treasury, &300);
+
+    // 100 dias: mas de tres veces el TTL de la instancia, renovado en cada llamada.
+    for _ in 0..5 {
+        env.ledger().with_mut(|ledger| {
+            ledger.sequence_number += 20 * TerraMarketplace::DAY_IN_LEDGERS;
+        });
+        assert_eq!(
+            client.get_network_payment_token(&Symbol::new(&env, "testnet")),
+            Some(payment_token.clone())
+        );
+    }
+    client.set_fee_config(&treasury, &250);
+}
+
+#[test]
+fn manual_payment_rate_goes_stale() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(TerraMarketplace, ());
+    let client = TerraMarketplaceClient::new(&env, &contract_id);
+    let admin = Address::generate(&env);
+    client.init(
+        &admin,
+        &Address::generate(&env),
+        &Address::generate(&env),
+        &Address::generate(&env),
+        &300,
+    );
+
+    let token = Address::generate(&env);
+    assert_eq!(
+        client.try_get_conversion_rate(&token),
+        Err(Ok(MarketplaceError::UnsupportedPaymentToken))
+    );
+    client.set_payment_rate(&token, &25_000_000, &3_600);
+    assert_eq!(client.get_conversion_rate(&token), 25_000_000);
+
+    env.ledger().with_mut(|ledger| ledger.timestamp += 3_601);
+    assert_eq!(
+        client.try_get_conversion_rate(&token),
+        Err(Ok(MarketplaceError::StalePrice))
+    );
+}
+
+#[test]
+fn payouts_are_pro_rata_across_deposits() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let (a, b, c) = (market.funded(10_000), market.funded(30_000), market.funded(10_000));
+    market.buy(&a, 100);
+    market.buy(&b, 300);
+    market.buy(&c, 100);
+    // La mitad de `c` queda en escrow por una orden de venta y sigue contando.
+    market
+        .client
+        .create_sell_order(&c, &market.asset_id, &50, &150, &(90 * DAY));
+    market.settle();
+
+    StellarAssetClient::new(&env, &market.token).mint(&market.seller, &1_333);
+    market.client.deposit_payout(&market.seller, &market.asset_id, &1_000);
+    assert_eq!(market.client.claim_payout(&a, &market.asset_id), 200);
+    assert_eq!(
+        market.client.try_claim_payout(&a, &market.asset_id),
+        Err(Ok(MarketplaceError::NothingToClaim))
+    );
+
+    // 333 / 500 tokens no es exacto: cada holder redondea hacia abajo.
+    market.client.deposit_payout(&market.seller, &market.asset_id, &333);
+    assert_eq!(market.client.get_pending_payout(&market.asset_id, &c), 266);
+    assert_eq!(market.client.claim_payout(&a, &market.asset_id), 66);
+    assert_eq!(market.client.claim_payout(&b, &market.asset_id), 799);
+    assert_eq!(market.client.claim_payout(&c, &market.asset_id), 266);
+
+    let pool = market.client.get_payout_pool(&market.asset_id).unwrap();
+    assert_eq!((pool.total_deposited, pool.total_claimed), (1_333, 1_331));
+    // `a` gasto todo en la compra; lo que tiene son sus dos cobros.
+    assert_eq!(market.balance(&a), 266);
+    assert_eq!(market.client.get_payout_position(&market.asset_id, &b).claimed, 799);
+}
+
+#[test]
+fn redeem_burns_with_or_without_payout() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let (a, b) = (market.funded(10_000), market.funded(30_000));
+    market.buy(&a, 100);
+    market.buy(&b, 300);
+    market.settle();
+
+    // Perdida total: el vendedor nunca deposita y el holder igual puede canjear.
+    assert_eq!(market.client.redeem(&a, &market.asset_id, &100), 0);
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &a), 0);
+    assert_eq!(market.tokenization.get_asset(&market.asset_id).circulating_supply, 300);
+
+    // El deposito posterior va entero a los 300 tokens que siguen en circulacion.
+    StellarAssetClient::new(&env, &market.token).mint(&market.seller, &600);
+    market.client.deposit_payout(&market.seller, &market.asset_id, &600);
+    assert_eq!(market.client.get_pending_payout(&market.asset_id, &a), 0);
+    assert_eq!(market.client.redeem(&b, &market.asset_id, &300), 600);
+    assert_eq!(market.balance(&b), 600);
+
+    let asset = market.tokenization.get_asset(&market.asset_id);
+    assert_eq!((asset.circulating_supply, asset.active), (0, false));
+    assert_eq!(
+        market.client.try_claim_payout(&a, &market.asset_id),
+        Err(Ok(MarketplaceError::NothingToClaim))
+    );
+}
+
+#[test]
+fn sell_orders_fill_partially_and_release_escrow() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let (a, b, c) = (market.funded(10_000), market.funded(10_000), Address::generate(&env));
+    market.buy(&a, 100);
+    let order_id = market
+        .client
+        .create_sell_order(&a, &market.asset_id, &60, &150, &DAY);
+
+    // Los tokens en escrow siguen siendo de `a` pero no se pueden transferir.
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &a), 40);
+    assert_eq!(market.tokenization.get_escrowed_balance(&market.asset_id, &a), 60);
+    assert_eq!(
+        market.tokenization.try_transfer(&a, &c, &market.asset_id, &50),
+        Err(Ok(TokenizationError::InsufficientBalance))
+    );
+
+    let receipt = market.client.fill_sell_order(&b, &order_id, &20);
+    assert_eq!((receipt.total_paid, receipt.fee_paid), (3_000, 90));
+    assert_eq!(market.balance(&a), 2_910);
+    assert_eq!(market.balance(&market.treasury), 300 + 90);
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &b), 20);
+    assert_eq!(market.client.get_sell_order(&order_id).quantity, 40);
+    assert_eq!(
+        market.client.try_fill_sell_order(&b, &order_id, &50).err(),
+        Some(Ok(MarketplaceError::InsufficientAvailableTokens))
+    );
+
+    // Un tercero solo puede cerrar la orden una vez vencida.
+    assert_eq!(
+        market.client.try_cancel_sell_order(&c, &order_id),
+        Err(Ok(MarketplaceError::OrderNotExpired))
+    );
+    market.advance(DAY);
+    assert_eq!(
+        market.client.try_fill_sell_order(&b, &order_id, &10).err(),
+        Some(Ok(MarketplaceError::OrderExpired))
+    );
+    assert_eq!(market.client.cancel_sell_order(&c, &order_id), 40);
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &a), 80);
+    assert_eq!(market.tokenization.get_escrowed_balance(&market.asset_id, &a), 0);
+    assert_eq!(
+        market.client.try_get_sell_order(&order_id).err(),
+        Some(Ok(MarketplaceError::OrderNotFound))
+    );
+}
+
+#[test]
+fn offers_lock_funds_until_accepted_or_refunded() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let (b, c) = (market.funded(10_000), market.funded(10_000));
+
+    let accepted = market
+        .client
+        .make_offer(&b, &market.asset_id, &50, &80, &DAY);
+    assert_eq!(market.balance(&b), 6_000);
+    assert_eq!(market.balance(&market.client.address), 4_000);
+    assert_eq!(
+        market.client.try_accept_offer(&c, &accepted).err(),
+        Some(Ok(MarketplaceError::Unauthorized))
+    );
+    let receipt = market.client.accept_offer(&market.seller, &accepted);
+    assert_eq!((receipt.total_paid, receipt.fee_paid), (4_000, 120));
+    assert_eq!(market.balance(&market.seller), 3_880);
+    assert_eq!(market.balance(&market.client.address), 0);
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &b), 50);
+
+    let expired = market
+        .client
+        .make_offer(&c, &market.asset_id, &10, &90, &DAY);
+    assert_eq!(
+        market.client.try_refund_offer(&expired),
+        Err(Ok(MarketplaceError::OfferNotExpired))
+    );
+    market.advance(DAY);
+    assert_eq!(
+        market.client.try_accept_offer(&market.seller, &expired).err(),
+        Some(Ok(MarketplaceError::OfferExpired))
+    );
+    assert_eq!(market.client.refund_offer(&expired), 900);
+    assert_eq!(market.balance(&c), 10_000);
+    assert_eq!(
+        market.client.try_get_offer(&expired).err(),
+        Some(Ok(MarketplaceError::OfferNotFound))
+    );
+}
+
+#[test]
+fn funding_goal_releases_escrow_on_success() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    market
+        .tokenization
+        .set_funding_goal(&market.seller, &market.asset_id, &300, &(10 * DAY));
+    let (a, b) = (market.funded(10_000), market.funded(20_000));
+
+    market.buy(&a, 100);
+    assert_eq!(market.balance(&market.client.address), 10_000);
+    assert_eq!(market.balance(&market.seller), 0);
+    assert_eq!(
+        market.tokenization.try_start_operation(&market.seller, &market.asset_id),
+        Err(Ok(TokenizationError::FundingGoalNotReached))
+    );
+
+    market.buy(&b, 200);
+    assert_eq!(market.client.finalize_funding(&market.asset_id), FundingStatus::Succeeded);
+    assert_eq!(market.balance(&market.seller), 29_100);
+    assert_eq!(market.balance(&market.treasury), 900);
+    assert_eq!(market.balance(&market.client.address), 0);
+    market.tokenization.start_operation(&market.seller, &market.asset_id);
+}
+
+#[test]
+fn failed_funding_refunds_buyers() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    market
+        .tokenization
+        .set_funding_goal(&market.seller, &market.asset_id, &500, &(10 * DAY));
+    let (a, b) = (market.funded(10_000), market.funded(10_000));
+    market.buy(&a, 100);
+    market.buy(&b, 50);
+
+    assert_eq!(
+        market.client.try_finalize_funding(&market.asset_id),
+        Err(Ok(MarketplaceError::FundingGoalNotReached))
+    );
+    market.advance(10 * DAY);
+    assert_eq!(
+        market
+            .client
+            .try_buy_tokens(&a, &market.asset_id, &1, &None, &market.token)
+            .err(),
+        Some(Ok(MarketplaceError::FundingClosed))
+    );
+    assert_eq!(market.client.finalize_funding(&market.asset_id), FundingStatus::Failed);
+    assert_eq!(
+        market.tokenization.try_start_operation(&market.seller, &market.asset_id),
+        Err(Ok(TokenizationError::FundingGoalNotReached))
+    );
+
+    assert_eq!(market.client.refund(&a, &market.asset_id), 10_000);
+    assert_eq!(market.balance(&a), 10_000);
+    assert_eq!(market.client.get_buyer_volume(&a), 0);
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &a), 0);
+    assert_eq!(
+        market.client.try_refund(&a, &market.asset_id),
+        Err(Ok(MarketplaceError::NothingToRefund))
+    );
+
+    // El ultimo reembolso borra la ronda y el activo admite una meta nueva.
+    assert_eq!(market.client.refund(&b, &market.asset_id), 5_000);
+    let asset = market.tokenization.get_asset(&market.asset_id);
+    assert_eq!((asset.available_tokens, asset.circulating_supply), (1_000, 0));
+    assert!(market.client.get_funding_pool(&market.asset_id).is_none());
+    market
+        .tokenization
+        .set_funding_goal(&market.seller, &market.asset_id, &100, &(20 * DAY));
+    market.buy(&a, 100);
+    assert_eq!(market.client.finalize_funding(&market.asset_id), FundingStatus::Succeeded);
+    assert_eq!(market.balance(&market.seller), 9_700);
+}
+
+#[test]
+fn buy_many_is_all_or_nothing_with_one_transfer_per_recipient() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let other_seller = Address::generate(&env);
+    let same_seller = market.new_asset(&market.seller);
+    let escrowed = market.new_asset(&market.seller);
+    let other = market.new_asset(&other_seller);
+    market
+        .tokenization
+        .set_funding_goal(&market.seller, &escrowed, &500, &(10 * DAY));
+    let buyer = market.funded(10_000);
+
+    let cart = vec![
+        &env,
+        (market.asset_id, 10_i128),
+        (same_seller, 10_i128),
+        (escrowed, 20_i128),
+        (other, 5_i128),
+    ];
+    assert_eq!(
+        market.client.try_buy_many(&buyer, &cart, &4_499).err(),
+        Some(Ok(MarketplaceError::SlippageExceeded))
+    );
+    let mut too_big = cart.clone();
+    too_big.set(3, (other, 5_000));
+    assert_eq!(
+        market.client.try_buy_many(&buyer, &too_big, &1_000_000).err(),
+        Some(Ok(MarketplaceError::InsufficientAvailableTokens))
+    );
+    assert_eq!(market.balance(&buyer), 10_000);
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &buyer), 0);
+
+    let receipts = market.client.buy_many(&buyer, &cart, &4_500);
+    assert_eq!(receipts.len(), 4);
+    // Seis tramos (vendedor y tesoreria por linea directa, escrow) agrupados en
+    // cuatro destinatarios: vendedor, tesoreria, escrow y el otro vendedor.
+    let transfers = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(contract, topics, _)| {
+            *contract == market.token
+                && Symbol::try_from_val(&env, &topics.get_unchecked(0)) == Ok(symbol_short!("transfer"))
+        })
+        .count();
+    assert_eq!(transfers, 4);
+    assert_eq!(market.balance(&market.seller), 970 + 970);
+    assert_eq!(market.balance(&market.treasury), 30 + 30 + 15);
+    assert_eq!(market.balance(&other_seller), 485);
+    assert_eq!(market.balance(&market.client.address), 2_000);
+    assert_eq!(market.client.get_contribution(&escrowed, &buyer).unwrap().quantity, 20);
+    assert_eq!(market.tokenization.get_buyer_balance(&other, &buyer), 5);
+}
+
+#[test]
+fn fee_resolution_prefers_asset_then_seller_then_global() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let other = market.new_asset(&market.seller);
+    let buyer = market.funded(10_000);
+    let fee = |asset_id: u64| market.client.get_effective_fee(&asset_id, &buyer);
+
+    assert_eq!(fee(market.asset_id), 300);
+    market.client.set_seller_fee(&market.seller, &Some(200));
+    market.client.set_asset_fee(&market.asset_id, &Some(500));
+    assert_eq!((fee(market.asset_id), fee(other)), (500, 200));
+
+    assert_eq!(market.buy(&buyer, 10).fee_paid, 50);
+    assert_eq!(market.client.get_buyer_volume(&buyer), 1_000);
+
+    // El tramo baja el 500 del activo pero no sube el 200 del vendedor.
+    market.client.set_fee_tiers(&vec![
+        &env,
+        FeeTier {
+            min_volume: 1_000,
+            fee_bps: 400,
+        },
+    ]);
+    assert_eq!((fee(market.asset_id), fee(other)), (400, 200));
+
+    market.client.set_asset_fee(&market.asset_id, &None);
+    market.client.set_seller_fee(&market.seller, &None);
+    assert_eq!((fee(market.asset_id), fee(other)), (300, 300));
+}
+
+#[test]
+fn referral_takes_its_share_of_the_fee() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let buyer = market.funded(10_000);
+    let (referrer, liquidity) = (Address::generate(&env), Address::generate(&env));
+    market.client.set_liquidity_config(&Some(liquidity.clone()), &2_000);
+    market.client.set_referral_config(&3_000);
+    assert_eq!(
+        market.client.try_set_referral_config(&8_001),
+        Err(Ok(MarketplaceError::InvalidReferralBps))
+    );
+    assert_eq!(
+        market
+            .client
+            .try_buy_tokens(&buyer, &market.asset_id, &100, &Some(buyer.clone()), &market.token)
+            .err(),
+        Some(Ok(MarketplaceError::InvalidReferrer))
+    );
+
+    // Fee 300: 90 al referido, 60 a liquidez y el resto a tesoreria.
+    let receipt = market.client.buy_tokens(
+        &buyer,
+        &market.asset_id,
+        &100,
+        &Some(referrer.clone()),
+        &market.token,
+    );
+    assert_eq!((receipt.fee_paid, receipt.referral_amount), (300, 90));
+    assert_eq!(market.balance(&referrer), 90);
+    assert_eq!(market.balance(&liquidity), 60);
+    assert_eq!(market.balance(&market.treasury), 150);
+    assert_eq!(market.balance(&market.seller), 9_700);
+    assert_eq!(market.client.get_referral_total(&referrer, &market.token), 90);
+}
+
+#[test]
+fn accrual_mode_credits_balances_for_withdraw() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let escrowed = market.new_asset(&market.seller);
+    market
+        .tokenization
+        .set_funding_goal(&market.seller, &escrowed, &500, &(10 * DAY));
+    market.client.set_accrual_mode(&true);
+    let buyer = market.funded(10_000);
+
+    market.client.buy_many(
+        &buyer,
+        &vec![&env, (market.asset_id, 10_i128), (escrowed, 20_i128)],
+        &3_000,
+    );
+    assert_eq!(market.balance(&market.client.address), 3_000);
+    assert_eq!(market.client.get_accrued(&market.seller, &market.token), 970);
+    assert_eq!(market.client.get_accrued(&market.treasury, &market.token), 30);
+    // El escrow de la ronda no aparece como saldo del propio marketplace.
+    assert_eq!(market.client.get_accrued(&market.client.address, &market.token), 0);
+
+    assert_eq!(market.client.withdraw(&market.seller, &market.token), 970);
+    assert_eq!(market.balance(&market.seller), 970);
+    assert_eq!(
+        market.client.try_withdraw(&market.seller, &market.token),
+        Err(Ok(MarketplaceError::NothingToWithdraw))
+    );
+
+    // Lo acumulado se puede retirar aunque se desactive el modo.
+    market.client.set_accrual_mode(&false);
+    assert_eq!(market.client.withdraw(&market.treasury, &market.token), 30);
+    assert_eq!(market.balance(&market.client.address), 2_000);
+}
+
+#[test]
+fn english_auction_refunds_outbid_and_settles_with_fee_split() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let (first, second) = (market.funded(20_000), market.funded(20_000));
+    let auction_id = market
+        .client
+        .create_english_auction(&market.seller, &market.asset_id, &100, &120, &DAY);
+
+    assert_eq!(
+        market.client.try_bid(&first, &auction_id, &110),
+        Err(Ok(Error::from(AuctionError::BidTooLow)))
+    );
+    market.client.bid(&first, &auction_id, &120);
+    assert_eq!(
+        market.client.try_bid(&second, &auction_id, &120),
+        Err(Ok(Error::from(AuctionError::BidTooLow)))
+    );
+    market.client.bid(&second, &auction_id, &130);
+
+    // La puja superada queda como saldo retirable del postor anterior.
+    assert_eq!(market.client.get_accrued(&first, &market.token), 12_000);
+    assert_eq!(market.client.withdraw(&first, &market.token), 12_000);
+    assert_eq!(market.balance(&first), 20_000);
+
+    assert_eq!(
+        market.client.try_settle_auction(&auction_id).err(),
+        Some(Ok(Error::from(AuctionError::AuctionNotEnded)))
+    );
+    market.advance(DAY);
+    assert_eq!(
+        market.client.try_bid(&first, &auction_id, &200),
+        Err(Ok(Error::from(AuctionError::AuctionEnded)))
+    );
+    let receipt = market.client.settle_auction(&auction_id).unwrap();
+    assert_eq!((receipt.total_paid, receipt.fee_paid), (13_000, 390));
+    assert_eq!(market.balance(&market.seller), 12_610);
+    assert_eq!(market.balance(&market.treasury), 390);
+    assert_eq!(market.balance(&market.client.address), 0);
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &second), 100);
+    assert_eq!(market.client.get_auction_reserved(&market.asset_id), 0);
+    assert_eq!(
+        market.client.try_get_auction(&auction_id).err(),
+        Some(Ok(Error::from(AuctionError::AuctionNotFound)))
+    );
+}
+
+#[test]
+fn failed_auction_settlement_returns_the_winning_bid() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let bidder = market.funded(20_000);
+    let auction_id = market
+        .client
+        .create_english_auction(&market.seller, &market.asset_id, &100, &120, &DAY);
+    market.client.bid(&bidder, &auction_id, &150);
+
+    // El activo deja de estar a la venta antes del cierre: no hay venta posible.
+    market
+        .tokenization
+        .set_asset_active(&market.seller, &market.asset_id, &false);
+    market.advance(DAY);
+    assert!(market.client.settle_auction(&auction_id).is_none());
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &bidder), 0);
+    assert_eq!(market.client.get_auction_reserved(&market.asset_id), 0);
+    assert_eq!(market.client.withdraw(&bidder, &market.token), 15_000);
+    assert_eq!(market.balance(&bidder), 20_000);
+    assert_eq!(market.balance(&market.seller), 0);
+}
+
+#[test]
+fn dutch_auction_price_falls_linearly() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let buyer = market.funded(20_000);
+    let auction_id = market
+        .client
+        .create_dutch_auction(&market.seller, &market.asset_id, &100, &200, &100, &1_000);
+
+    assert_eq!(market.client.get_auction_price(&auction_id), 200);
+    market.advance(500);
+    assert_eq!(market.client.get_auction_price(&auction_id), 150);
+    market.advance(499);
+    assert_eq!(market.client.get_auction_price(&auction_id), 101);
+    market.advance(1);
+    assert_eq!(market.client.get_auction_price(&auction_id), 100);
+
+    let auction_id = market
+        .client
+        .create_dutch_auction(&market.seller, &market.asset_id, &100, &200, &100, &2_000);
+    market.advance(500);
+    assert_eq!(
+        market.client.try_take_dutch_auction(&buyer, &auction_id, &149).err(),
+        Some(Ok(Error::from(MarketplaceError::SlippageExceeded)))
+    );
+    let receipt = market.client.take_dutch_auction(&buyer, &auction_id, &150);
+    assert_eq!((receipt.quantity, receipt.total_paid), (100, 15_000));
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &buyer), 100);
+}
+
+#[test]
+fn auction_reservation_is_not_sold_elsewhere() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let buyer = market.funded(100_000);
+    let auction_id = market
+        .client
+        .create_english_auction(&market.seller, &market.asset_id, &100, &120, &DAY);
+    assert_eq!(market.client.get_auction_reserved(&market.asset_id), 100);
+
+    // Tokenizacion consulta la reserva: el vendedor no puede retirarla de la emision.
+    assert_eq!(
+        market.tokenization.try_decrease_supply(&market.seller, &market.asset_id, &901),
+        Err(Ok(TokenizationError::InsufficientAvailableTokens))
+    );
+
+    // Repetir el activo en el carrito no saltea la reserva.
+    let cart = vec![&env, (market.asset_id, 500_i128), (market.asset_id, 500_i128)];
+    assert_eq!(
+        market.client.try_buy_many(&buyer, &cart, &1_000_000).err(),
+        Some(Ok(MarketplaceError::InvalidCart))
+    );
+
+    assert_eq!(
+        market
+            .client
+            .try_buy_tokens(&buyer, &market.asset_id, &901, &None, &market.token)
+            .err(),
+        Some(Ok(MarketplaceError::InsufficientAvailableTokens))
+    );
+    market.buy(&buyer, 900);
+    assert_eq!(
+        market
+            .client
+            .try_create_english_auction(&market.seller, &market.asset_id, &1, &120, &DAY),
+        Err(Ok(Error::from(MarketplaceError::InsufficientAvailableTokens)))
+    );
+
+    // Cancelar sin pujas libera la reserva para la venta directa.
+    market.client.cancel_auction(&market.seller, &auction_id);
+    assert_eq!(market.client.get_auction_reserved(&market.asset_id), 0);
+    market.buy(&buyer, 100);
+    assert_eq!(market.tokenization.get_asset(&market.asset_id).available_tokens, 0);
+}
+
+#[test]
+fn alternative_token_is_converted_rounding_up() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let usdc = env.register_stellar_asset_contract_v2(Address::generate(&env)).address();
+    market.client.set_payment_rate(&usdc, &13_333_333, &DAY);
+    market.client.set_referral_config(&3_000);
+    let (buyer, referrer) = (Address::generate(&env), Address::generate(&env));
+    StellarAssetClient::new(&env, &usdc).mint(&buyer, &20_000);
+
+    // 10_000 base * 1.3333333 = 13_333.333 -> 13_334; fee 300/10_000 y referido 90/300.
+    let preview = market
+        .client
+        .preview_purchase(&buyer, &market.asset_id, &100, &Some(referrer.clone()), &usdc);
+    assert_eq!((preview.total_paid, preview.quoted_total), (13_334, 10_000));
+    assert_eq!((preview.fee_paid, preview.referral_amount), (400, 120));
+    assert_eq!(preview.seller_amount, 12_934);
+
+    let receipt = market
+        .client
+        .buy_tokens(&buyer, &market.asset_id, &100, &Some(referrer.clone()), &usdc);
+    assert_eq!((receipt.total_paid, receipt.fee_paid), (13_334, 400));
+    let usdc_balance = |account: &Address| TokenClient::new(&env, &usdc).balance(account);
+    assert_eq!(usdc_balance(&buyer), 6_666);
+    assert_eq!(usdc_balance(&market.seller), 12_934);
+    assert_eq!(usdc_balance(&referrer), 120);
+    assert_eq!(market.client.get_referral_total(&referrer, &usdc), 120);
+    assert_eq!(market.client.get_referral_total(&referrer, &market.token), 0);
+    assert_eq!(usdc_balance(&market.treasury), 280);
+    assert_eq!(market.client.get_buyer_volume(&buyer), 10_000);
+}
+
+#[test]
+fn funding_round_only_takes_the_base_token() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    market
+        .tokenization
+        .set_funding_goal(&market.seller, &market.asset_id, &500, &(10 * DAY));
+    let usdc = env.register_stellar_asset_contract_v2(Address::generate(&env)).address();
+    market.client.set_payment_rate(&usdc, &20_000_000, &(30 * DAY));
+    let early = Address::generate(&env);
+    StellarAssetClient::new(&env, &usdc).mint(&early, &20_000);
+
+    // Comprar primero con otro token no fija la ronda a ese token.
+    assert_eq!(
+        market
+            .client
+            .try_buy_tokens(&early, &market.asset_id, &100, &None, &usdc)
+            .err(),
+        Some(Ok(MarketplaceError::PaymentTokenMismatch))
+    );
+    let buyer = market.funded(10_000);
+    market.buy(&buyer, 100);
+    let pool = market.client.get_funding_pool(&market.asset_id).unwrap();
+    assert_eq!(pool.payment_token, market.token);
+    let contribution = market.client.get_contribution(&market.asset_id, &buyer).unwrap();
+    assert_eq!((contribution.paid, contribution.quoted_total), (10_000, 10_000));
+
+    market.advance(10 * DAY);
+    market.client.finalize_funding(&market.asset_id);
+    assert_eq!(market.client.refund(&buyer, &market.asset_id), 10_000);
+    assert_eq!(market.client.get_buyer_volume(&buyer), 0);
+    assert_eq!(market.balance(&buyer), 10_000);
+}
+
+#[test]
+fn admin_handover_and_roles_need_the_right_signer() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let admin = market.client.get_admin();
+    let (next, fee_manager) = (Address::generate(&env), Address::generate(&env));
+
+    assert_eq!(market.client.try_accept_admin(), Err(Ok(MarketplaceError::NoPendingAdmin)));
+    market.client.propose_admin(&next);
+    assert_eq!(market.client.get_admin(), admin);
+    market.client.accept_admin();
+    assert_eq!(env.auths()[0].0, next);
+    assert_eq!(market.client.get_pending_admin(), None);
+    assert_eq!(market.client.try_accept_admin(), Err(Ok(MarketplaceError::NoPendingAdmin)));
+
+    // Sin titular el rol lo ejerce el admin; con titular, solo el titular.
+    market.client.set_referral_config(&2_500);
+    assert_eq!(env.auths()[0].0, next);
+    market.client.set_role(&Role::FeeManager, &Some(fee_manager.clone()));
+    market.client.set_referral_config(&2_000);
+    assert_eq!(env.auths()[0].0, fee_manager);
+    let as_admin = MockAuth {
+        address: &next,
+        invoke: &MockAuthInvoke {
+            contract: &market.client.address,
+            fn_name: "set_referral_config",
+            args: (1_000_i128,).into_val(&env),
+            sub_invokes: &[],
+        },
+    };
+    assert!(market.client.mock_auths(&[as_admin]).try_set_referral_config(&1_000).is_err());
+    assert_eq!(market.client.get_referral_share_bps(), 2_000);
+}
+
+#[test]
+fn pauses_block_sales_claims_and_withdrawals() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let other = market.new_asset(&market.seller);
+    market.client.set_accrual_mode(&true);
+    let (a, b) = (market.funded(10_000), market.funded(10_000));
+    market.buy(&a, 100);
+    market.settle();
+    StellarAssetClient::new(&env, &market.token).mint(&market.seller, &500);
+    market.client.deposit_payout(&market.seller, &market.asset_id, &500);
+
+    market.client.set_paused(&true);
+    assert_eq!(
+        market.client.try_buy_tokens(&b, &other, &10, &None, &market.token).err(),
+        Some(Ok(MarketplaceError::Paused))
+    );
+    assert_eq!(
+        market.client.try_claim_payout(&a, &market.asset_id),
+        Err(Ok(MarketplaceError::Paused))
+    );
+    assert_eq!(
+        market.client.try_withdraw(&market.seller, &market.token),
+        Err(Ok(MarketplaceError::Paused))
+    );
+    market.client.set_paused(&false);
+
+    // La pausa por activo frena ese activo; los saldos acumulados se retiran igual.
+    market.client.set_asset_paused(&market.asset_id, &true);
+    assert_eq!(
+        market.client.try_claim_payout(&a, &market.asset_id),
+        Err(Ok(MarketplaceError::Paused))
+    );
+    market.client.buy_tokens(&b, &other, &10, &None, &market.token);
+    assert_eq!(market.client.withdraw(&market.seller, &market.token), 9_700 + 970);
+    market.client.set_asset_paused(&market.asset_id, &false);
+    assert_eq!(market.client.claim_payout(&a, &market.asset_id), 500);
+}
+
+#[test]
+fn buy_with_limits_guards_price_and_deadline() {
+    let env = Env::default();
+    let market = setup_market(&env);
+    let buyer = market.funded(30_000);
+    let preview = market
+        .client
+        .preview_purchase(&buyer, &market.asset_id, &100, &None, &market.token);
+    assert_eq!(preview.total_paid, 10_000);
+    let deadline = env.ledger().timestamp() + 60;
+
+    // El vendedor sube el precio entre la cotizacion y la compra.
+    market.tokenization.update_price(&market.seller, &market.asset_id, &120);
+    assert_eq!(
+        market
+            .client
+            .try_buy_tokens_with_limits(
+                &buyer,
+                &market.asset_id,
+                &100,
+                &preview.total_paid,
+                &deadline,
+                &None,
+                &market.token,
+            )
+            .err(),
+        Some(Ok(MarketplaceError::SlippageExceeded))
+    );
+    market.tokenization.update_price(&market.seller, &market.asset_id, &100);
+    let receipt = market.client.buy_tokens_with_limits(
+        &buyer,
+        &market.asset_id,
+        &100,
+        &preview.total_paid,
+        &deadline,
+        &None,
+        &market.token,
+    );
+    assert_eq!((receipt.total_paid, receipt.fee_paid), (preview.total_paid, preview.fee_paid));
+    assert_eq!(market.balance(&buyer), 20_000);
+
+    market.advance(61);
+    assert_eq!(
+        market
+            .client
+            .try_buy_tokens_with_limits(
+                &buyer,
+                &market.asset_id,
+                &100,
+                &preview.total_paid,
+                &deadline,
+                &None,
+                &market.token,
+            )
+            .err(),
+        Some(Ok(MarketplaceError::DeadlineExpired))
+    );
+    assert_eq!(market.tokenization.get_buyer_balance(&market.asset_id, &buyer), 100);
+}
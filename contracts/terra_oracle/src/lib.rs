@@ -0,0 +1,319 @@
+#![no_std]
+
+use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Address, BytesN, Env, String, Vec};
+
+pub use terra_types::{FeedData, OracleError, PriceData};
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Admin,
+    PendingAdmin,
+    Reporters,
+    MaxAge,
+    MinReports,
+    Report((String, Address)),
+}
+
+// Ultimo dato publicado por un reporter para un feed. Precios con escala 10^7.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Report {
+    pub price: i128,
+    pub yield_bps: i128,
+    pub timestamp: u64,
+}
+
+#[contract]
+pub struct TerraOracle;
+
+#[contractimpl]
+impl TerraOracle {
+    const VERSION: u32 = 1;
+    const MAX_REPORTERS: u32 = 20;
+    const MAX_FEED_LEN: u32 = 32;
+    const DAY_IN_LEDGERS: u32 = 17_280;
+    const INSTANCE_BUMP_AMOUNT: u32 = 30 * Self::DAY_IN_LEDGERS;
+    const INSTANCE_LIFETIME_THRESHOLD: u32 = Self::INSTANCE_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+    const PERSISTENT_BUMP_AMOUNT: u32 = 60 * Self::DAY_IN_LEDGERS;
+    const PERSISTENT_LIFETIME_THRESHOLD: u32 = Self::PERSISTENT_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+
+    pub fn init(env: Env, admin: Address, max_age: u64, min_reports: u32) -> Result<(), OracleError> {
+        Self::extend_instance_ttl(&env);
+        if env.storage().instance().has(&DataKey::Admin) {
+            return Err(OracleError::AlreadyInitialized);
+        }
+        admin.require_auth();
+        Self::validate_config(max_age, min_reports)?;
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage().instance().set(&DataKey::MaxAge, &max_age);
+        env.storage().instance().set(&DataKey::MinReports, &min_reports);
+        env.storage()
+            .instance()
+            .set(&DataKey::Reporters, &Vec::<Address>::new(&env));
+        env.events().publish((symbol_short!("init"),), admin);
+        Ok(())
+    }
+
+    pub fn propose_admin(env: Env, new_admin: Address) -> Result<(), OracleError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::PendingAdmin, &new_admin);
+        env.events()
+            .publish((symbol_short!("admin"), symbol_short!("proposed")), new_admin);
+        Ok(())
+    }
+
+    pub fn accept_admin(env: Env) -> Result<(), OracleError> {
+        Self::extend_instance_ttl(&env);
+        let pending = env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::PendingAdmin)
+            .ok_or(OracleError::NoPendingAdmin)?;
+        pending.require_auth();
+        env.storage().instance().set(&DataKey::Admin, &pending);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+        env.events()
+            .publish((symbol_short!("admin"), symbol_short!("accepted")), pending);
+        Ok(())
+    }
+
+    pub fn get_admin(env: Env) -> Result<Address, OracleError> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::Admin)
+            .ok_or(OracleError::NotInitialized)
+    }
+
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), OracleError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+        env.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+        env.events().publish((symbol_short!("upgrade"),), new_wasm_hash);
+        Ok(())
+    }
+
+    pub fn version() -> u32 {
+        Self::VERSION
+    }
+
+    // `max_age`: segundos que un reporte sigue vigente. `min_reports`: reportes
+    // vigentes necesarios para publicar una mediana.
+    pub fn set_config(env: Env, max_age: u64, min_reports: u32) -> Result<(), OracleError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+        Self::validate_config(max_age, min_reports)?;
+        env.storage().instance().set(&DataKey::MaxAge, &max_age);
+        env.storage().instance().set(&DataKey::MinReports, &min_reports);
+        env.events()
+            .publish((symbol_short!("config"),), (max_age, min_reports));
+        Ok(())
+    }
+
+    pub fn get_config(env: Env) -> (u64, u32) {
+        Self::extend_instance_ttl(&env);
+        (Self::get_max_age(&env), Self::get_min_reports(&env))
+    }
+
+    pub fn set_reporter(env: Env, reporter: Address, enabled: bool) -> Result<(), OracleError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+        let mut reporters = Self::get_reporters(env.clone());
+        let index = reporters.first_index_of(&reporter);
+        match (enabled, index) {
+            (true, None) => {
+                if reporters.len() >= Self::MAX_REPORTERS {
+                    return Err(OracleError::TooManyReporters);
+                }
+                reporters.push_back(reporter.clone());
+            }
+            (false, Some(index)) => {
+                reporters.remove(index);
+            }
+            _ => {}
+        }
+        env.storage().instance().set(&DataKey::Reporters, &reporters);
+        env.events()
+            .publish((symbol_short!("reporter"), reporter), enabled);
+        Ok(())
+    }
+
+    pub fn get_reporters(env: Env) -> Vec<Address> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Vec<Address>>(&DataKey::Reporters)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    // Feeds por categoria (`cultivo`, `tierra`, `ganaderia`) para valuacion y por
+    // par (ej. `XLM/USDT`) para conversion de pagos. `timestamp` es el momento
+    // de la observacion y debe ser mas nuevo que el ultimo reporte del mismo reporter.
+    pub fn report(
+        env: Env,
+        reporter: Address,
+        feed: String,
+        price: i128,
+        yield_bps: i128,
+        timestamp: u64,
+    ) -> Result<(), OracleError> {
+        Self::extend_instance_ttl(&env);
+        reporter.require_auth();
+        if !Self::get_reporters(env.clone()).contains(&reporter) {
+            return Err(OracleError::Unauthorized);
+        }
+        if feed.is_empty() || feed.len() > Self::MAX_FEED_LEN {
+            return Err(OracleError::InvalidFeed);
+        }
+        if price <= 0 || yield_bps < 0 {
+            return Err(OracleError::InvalidPrice);
+        }
+        let now = env.ledger().timestamp();
+        if timestamp > now {
+            return Err(OracleError::InvalidTimestamp);
+        }
+        if now - timestamp > Self::get_max_age(&env) {
+            return Err(OracleError::StalePrice);
+        }
+        let key = DataKey::Report((feed.clone(), reporter.clone()));
+        if let Some(previous) = env.storage().persistent().get::<DataKey, Report>(&key) {
+            if timestamp <= previous.timestamp {
+                return Err(OracleError::InvalidTimestamp);
+            }
+        }
+
+        let report = Report {
+            price,
+            yield_bps,
+            timestamp,
+        };
+        env.storage().persistent().set(&key, &report);
+        Self::extend_persistent_ttl(&env, &key);
+        env.events()
+            .publish((symbol_short!("report"), feed, reporter), report);
+        Ok(())
+    }
+
+    pub fn get_report(env: Env, feed: String, reporter: Address) -> Option<Report> {
+        Self::extend_instance_ttl(&env);
+        Self::read_report(&env, &feed, &reporter)
+    }
+
+    // Mediana de precio y rendimiento entre los reporters habilitados con reporte
+    // vigente. Falla si no alcanzan `min_reports`.
+    pub fn get_feed(env: Env, feed: String) -> Result<FeedData, OracleError> {
+        Self::extend_instance_ttl(&env);
+        let now = env.ledger().timestamp();
+        let max_age = Self::get_max_age(&env);
+        let mut prices: Vec<i128> = Vec::new(&env);
+        let mut yields: Vec<i128> = Vec::new(&env);
+        let mut oldest = u64::MAX;
+        let mut stale = false;
+        for reporter in Self::get_reporters(env.clone()).iter() {
+            let Some(report) = Self::read_report(&env, &feed, &reporter) else {
+                continue;
+            };
+            if now.saturating_sub(report.timestamp) > max_age {
+                stale = true;
+                continue;
+            }
+            Self::insert_sorted(&mut prices, report.price);
+            Self::insert_sorted(&mut yields, report.yield_bps);
+            oldest = oldest.min(report.timestamp);
+        }
+
+        if prices.is_empty() || prices.len() < Self::get_min_reports(&env) {
+            return Err(if stale {
+                OracleError::StalePrice
+            } else {
+                OracleError::InsufficientReports
+            });
+        }
+        Ok(FeedData {
+            price: Self::median(&prices),
+            yield_bps: Self::median(&yields),
+            timestamp: oldest,
+            sources: prices.len(),
+        })
+    }
+
+    // Vista reducida de `get_feed` para consumidores que solo necesitan precio.
+    pub fn get_price(env: Env, feed: String) -> Result<PriceData, OracleError> {
+        let data = Self::get_feed(env, feed)?;
+        Ok(PriceData {
+            value: data.price,
+            timestamp: data.timestamp,
+        })
+    }
+
+    fn validate_config(max_age: u64, min_reports: u32) -> Result<(), OracleError> {
+        if max_age == 0 || min_reports == 0 || min_reports > Self::MAX_REPORTERS {
+            return Err(OracleError::InvalidConfig);
+        }
+        Ok(())
+    }
+
+    fn insert_sorted(values: &mut Vec<i128>, value: i128) {
+        let mut index = 0;
+        while index < values.len() && values.get_unchecked(index) <= value {
+            index += 1;
+        }
+        values.insert(index, value);
+    }
+
+    // Con cantidad par se promedian los dos valores centrales.
+    fn median(sorted: &Vec<i128>) -> i128 {
+        let middle = sorted.len() / 2;
+        let upper = sorted.get_unchecked(middle);
+        if sorted.len() % 2 == 1 {
+            return upper;
+        }
+        let lower = sorted.get_unchecked(middle - 1);
+        lower + (upper - lower) / 2
+    }
+
+    fn get_max_age(env: &Env) -> u64 {
+        env.storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::MaxAge)
+            .unwrap_or(0)
+    }
+
+    fn get_min_reports(env: &Env) -> u32 {
+        env.storage()
+            .instance()
+            .get::<DataKey, u32>(&DataKey::MinReports)
+            .unwrap_or(1)
+    }
+
+    fn read_report(env: &Env, feed: &String, reporter: &Address) -> Option<Report> {
+        let key = DataKey::Report((feed.clone(), reporter.clone()));
+        let report = env.storage().persistent().get::<DataKey, Report>(&key);
+        if report.is_some() {
+            Self::extend_persistent_ttl(env, &key);
+        }
+        report
+    }
+
+    fn extend_instance_ttl(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(Self::INSTANCE_LIFETIME_THRESHOLD, Self::INSTANCE_BUMP_AMOUNT);
+    }
+
+    fn extend_persistent_ttl(env: &Env, key: &DataKey) {
+        env.storage().persistent().extend_ttl(
+            key,
+            Self::PERSISTENT_LIFETIME_THRESHOLD,
+            Self::PERSISTENT_BUMP_AMOUNT,
+        );
+    }
+}
+
+mod test;
@@ -0,0 +1,47 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Ledger};
+
+#[test]
+fn feed_uses_median_of_fresh_reports() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|ledger| ledger.timestamp = 10_000);
+
+    let contract_id = env.register(TerraOracle, ());
+    let client = TerraOracleClient::new(&env, &contract_id);
+    client.init(&Address::generate(&env), &3_600, &2);
+
+    let feed = String::from_str(&env, "cultivo");
+    let reporters = [
+        Address::generate(&env),
+        Address::generate(&env),
+        Address::generate(&env),
+    ];
+    for reporter in reporters.iter() {
+        client.set_reporter(reporter, &true);
+    }
+    client.report(&reporters[0], &feed, &100, &800, &9_000);
+    assert_eq!(
+        client.try_get_feed(&feed),
+        Err(Ok(OracleError::InsufficientReports))
+    );
+
+    client.report(&reporters[1], &feed, &130, &500, &9_500);
+    client.report(&reporters[2], &feed, &400, &600, &9_800);
+    let data = client.get_feed(&feed);
+    assert_eq!((data.price, data.yield_bps, data.timestamp, data.sources), (130, 600, 9_000, 3));
+
+    // El reporte de 9_000 vence y la mediana queda entre los dos restantes.
+    env.ledger().with_mut(|ledger| ledger.timestamp = 12_700);
+    let price = client.get_price(&feed);
+    assert_eq!((price.value, price.timestamp), (265, 9_500));
+
+    env.ledger().with_mut(|ledger| ledger.timestamp = 13_200);
+    assert_eq!(client.try_get_price(&feed), Err(Ok(OracleError::StalePrice)));
+    assert_eq!(
+        client.try_report(&reporters[2], &feed, &410, &600, &9_800),
+        Err(Ok(OracleError::InvalidTimestamp))
+    );
+}
@@ -1,28 +1,59 @@
 #![no_std]
 
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Map, String};
+use soroban_sdk::{
+    contract, contractimpl, contracttype, symbol_short, Address, BytesN, Env, Map, String, Symbol,
+    Vec,
+};
+
+pub use terra_types::{Asset, LifecycleStatus, PurchaseLimits, TokenizationError, WalletCap};
+use terra_types::MarketplaceClient;
+
+// Roles operativos: si un rol no tiene titular asignado lo ejerce el admin.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Role {
+    Pauser,
+    ComplianceOfficer,
+}
 
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
     Admin,
+    PendingAdmin,
+    RoleHolder(Role),
+    SchemaVersion,
     Marketplace,
     Asset(u64),
     NextAssetId,
     Balance((u64, Address)),
+    Allowance((u64, Address, Address)),
+    Paused,
+    AssetPaused(u64),
+    Verified(Address),
+    ComplianceRequired,
+    AssetComplianceRequired(u64),
+    Escrow((u64, Address)),
+    // Un punto por entrada, indexado desde 0, para que el historial no crezca
+    // dentro de una sola entrada del ledger.
+    PriceHistory((u64, u32)),
+    PriceHistoryLen(u64),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Verification {
+    pub jurisdiction: Symbol,
+    pub expires_at: u64,
+}
+
+// Foto de precio y emision despues de cada cambio, para auditar el historial.
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[contracttype]
-pub struct Asset {
-    pub id: u64,
-    pub seller: Address,
-    pub category: String,
-    pub title: String,
+pub struct PricePoint {
+    pub timestamp: u64,
     pub price_per_token: i128,
     pub total_tokens: i128,
-    pub available_tokens: i128,
-    pub active: bool,
 }
 
 #[contract]
@@ -31,32 +62,293 @@ pub struct TerraTokenization;
 #[contractimpl]
 impl TerraTokenization {
     const MAX_TEXT_LEN: u32 = 120;
+    const SECONDS_PER_DAY: u64 = 86_400;
+    const VERSION: u32 = 1;
+    const SCHEMA_VERSION: u32 = 1;
+    const DAY_IN_LEDGERS: u32 = 17_280;
+    const INSTANCE_BUMP_AMOUNT: u32 = 30 * Self::DAY_IN_LEDGERS;
+    const INSTANCE_LIFETIME_THRESHOLD: u32 = Self::INSTANCE_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+    const PERSISTENT_BUMP_AMOUNT: u32 = 60 * Self::DAY_IN_LEDGERS;
+    const PERSISTENT_LIFETIME_THRESHOLD: u32 = Self::PERSISTENT_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+    const MAX_PAGE_SIZE: u32 = 50;
 
-    pub fn init(env: Env, admin: Address) {
+    pub fn init(env: Env, admin: Address) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
         if env.storage().instance().has(&DataKey::Admin) {
-            panic!("already initialized");
+            return Err(TokenizationError::AlreadyInitialized);
         }
         admin.require_auth();
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::NextAssetId, &1_u64);
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &Self::SCHEMA_VERSION);
+        env.events().publish((symbol_short!("init"),), admin);
+        Ok(())
+    }
+
+    pub fn set_marketplace(env: Env, marketplace: Address) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::Marketplace, &marketplace);
+        env.events().publish((symbol_short!("mkt_set"),), marketplace);
+        Ok(())
+    }
+
+    pub fn propose_admin(env: Env, new_admin: Address) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::PendingAdmin, &new_admin);
+        env.events()
+            .publish((symbol_short!("admin"), symbol_short!("proposed")), new_admin);
+        Ok(())
     }
 
-    pub fn set_marketplace(env: Env, marketplace: Address) {
-        let admin = env
+    pub fn accept_admin(env: Env) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let pending = env
             .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::PendingAdmin)
+            .ok_or(TokenizationError::NoPendingAdmin)?;
+        pending.require_auth();
+        env.storage().instance().set(&DataKey::Admin, &pending);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+        env.events()
+            .publish((symbol_short!("admin"), symbol_short!("accepted")), pending);
+        Ok(())
+    }
+
+    pub fn get_admin(env: Env) -> Result<Address, TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
             .instance()
             .get::<DataKey, Address>(&DataKey::Admin)
-            .unwrap_or_else(|| panic!("not initialized"));
+            .ok_or(TokenizationError::NotInitialized)
+    }
+
+    pub fn get_pending_admin(env: Env) -> Option<Address> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::PendingAdmin)
+    }
+
+    pub fn set_role(env: Env, role: Role, holder: Option<Address>) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
         admin.require_auth();
-        env.storage().instance().set(&DataKey::Marketplace, &marketplace);
+        match holder.clone() {
+            Some(address) => env
+                .storage()
+                .instance()
+                .set(&DataKey::RoleHolder(role), &address),
+            None => env.storage().instance().remove(&DataKey::RoleHolder(role)),
+        }
+        env.events().publish((symbol_short!("role"), role), holder);
+        Ok(())
+    }
+
+    pub fn get_role(env: Env, role: Role) -> Option<Address> {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::RoleHolder(role))
+    }
+
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+        env.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+        env.events().publish((symbol_short!("upgrade"),), new_wasm_hash);
+        Ok(())
+    }
+
+    // Se invoca despues de `upgrade` cuando el nuevo WASM cambia el formato del
+    // storage. Cada paso lleva el esquema de `n` a `n + 1`.
+    pub fn migrate(env: Env) -> Result<u32, TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+
+        let from = Self::get_schema_version(env.clone());
+        if from >= Self::SCHEMA_VERSION {
+            return Err(TokenizationError::AlreadyMigrated);
+        }
+
+        // v0 -> v1: despliegues previos a la llave `SchemaVersion`; no hay datos
+        // que transformar, solo se registra la version.
+
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &Self::SCHEMA_VERSION);
+        env.events()
+            .publish((symbol_short!("migrate"),), (from, Self::SCHEMA_VERSION));
+        Ok(Self::SCHEMA_VERSION)
+    }
+
+    pub fn version() -> u32 {
+        Self::VERSION
+    }
+
+    pub fn get_schema_version(env: Env) -> u32 {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, u32>(&DataKey::SchemaVersion)
+            .unwrap_or(0)
+    }
+
+    pub fn set_paused(env: Env, paused: bool) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::Pauser)?;
+        env.storage().instance().set(&DataKey::Paused, &paused);
+        env.events().publish((symbol_short!("paused"),), paused);
+        Ok(())
+    }
+
+    pub fn set_asset_paused(env: Env, asset_id: u64, paused: bool) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::Pauser)?;
+        if Self::read_asset(&env, asset_id).is_none() {
+            return Err(TokenizationError::AssetNotFound);
+        }
+        let key = DataKey::AssetPaused(asset_id);
+        if paused {
+            env.storage().persistent().set(&key, &true);
+            Self::extend_persistent_ttl(&env, &key);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+        env.events().publish((symbol_short!("paused"), asset_id), paused);
+        Ok(())
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        Self::extend_instance_ttl(&env);
+        env.storage()
+            .instance()
+            .get::<DataKey, bool>(&DataKey::Paused)
+            .unwrap_or(false)
+    }
+
+    pub fn is_asset_paused(env: Env, asset_id: u64) -> bool {
+        Self::extend_instance_ttl(&env);
+        let key = DataKey::AssetPaused(asset_id);
+        if env.storage().persistent().has(&key) {
+            Self::extend_persistent_ttl(&env, &key);
+            return true;
+        }
+        false
+    }
+
+    pub fn set_verification(
+        env: Env,
+        investor: Address,
+        jurisdiction: Symbol,
+        expires_at: u64,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::ComplianceOfficer)?;
+        if expires_at <= env.ledger().timestamp() {
+            return Err(TokenizationError::InvalidVerification);
+        }
+
+        let verification = Verification {
+            jurisdiction,
+            expires_at,
+        };
+        let key = DataKey::Verified(investor.clone());
+        env.storage().persistent().set(&key, &verification);
+        Self::extend_persistent_ttl(&env, &key);
+        env.events()
+            .publish((symbol_short!("kyc"), symbol_short!("verified"), investor), verification);
+        Ok(())
+    }
+
+    pub fn revoke_verification(env: Env, investor: Address) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::ComplianceOfficer)?;
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Verified(investor.clone()));
+        env.events()
+            .publish((symbol_short!("kyc"), symbol_short!("revoked"), investor), ());
+        Ok(())
+    }
+
+    pub fn set_compliance_required(env: Env, required: bool) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::ComplianceOfficer)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::ComplianceRequired, &required);
+        env.events()
+            .publish((symbol_short!("kyc"), symbol_short!("required")), required);
+        Ok(())
+    }
+
+    pub fn set_asset_compliance_required(
+        env: Env,
+        asset_id: u64,
+        required: bool,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_role(env.clone(), Role::ComplianceOfficer)?;
+        if Self::read_asset(&env, asset_id).is_none() {
+            return Err(TokenizationError::AssetNotFound);
+        }
+        let key = DataKey::AssetComplianceRequired(asset_id);
+        if required {
+            env.storage().persistent().set(&key, &true);
+            Self::extend_persistent_ttl(&env, &key);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+        env.events().publish(
+            (symbol_short!("kyc"), symbol_short!("required"), asset_id),
+            required,
+        );
+        Ok(())
+    }
+
+    pub fn get_verification(env: Env, investor: Address) -> Option<Verification> {
+        Self::extend_instance_ttl(&env);
+        let key = DataKey::Verified(investor);
+        let verification = env.storage().persistent().get::<DataKey, Verification>(&key);
+        if verification.is_some() {
+            Self::extend_persistent_ttl(&env, &key);
+        }
+        verification
+    }
+
+    pub fn is_verified(env: Env, investor: Address) -> bool {
+        Self::extend_instance_ttl(&env);
+        match Self::get_verification(env.clone(), investor) {
+            Some(verification) => verification.expires_at > env.ledger().timestamp(),
+            None => false,
+        }
+    }
+
+    // Un inversor puede recibir tokens de un activo si el activo no exige
+    // verificacion (ni global ni puntual) o si su verificacion sigue vigente.
+    pub fn is_eligible(env: Env, asset_id: u64, investor: Address) -> bool {
+        Self::extend_instance_ttl(&env);
+        !Self::is_compliance_required(&env, asset_id) || Self::is_verified(env, investor)
     }
 
     pub fn get_marketplace(env: Env) -> Option<Address> {
+        Self::extend_instance_ttl(&env);
         env.storage()
             .instance()
             .get::<DataKey, Address>(&DataKey::Marketplace)
     }
 
+    // `limits = None` crea el activo sin minimo de compra ni tope por billetera.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_asset(
         env: Env,
         seller: Address,
@@ -64,18 +356,33 @@ impl TerraTokenization {
         title: String,
         price_per_token: i128,
         total_tokens: i128,
-    ) -> u64 {
+        cycle_duration_days: u32,
+        limits: Option<PurchaseLimits>,
+    ) -> Result<u64, TokenizationError> {
+        Self::extend_instance_ttl(&env);
         seller.require_auth();
+        if Self::is_paused(env.clone()) {
+            return Err(TokenizationError::Paused);
+        }
 
         if price_per_token <= 0 || total_tokens <= 0 {
-            panic!("invalid asset values");
+            return Err(TokenizationError::InvalidAssetValues);
         }
-        if category.len() == 0 || category.len() > Self::MAX_TEXT_LEN {
-            panic!("invalid category length");
+        // Mismos ciclos que acepta la base de datos (30, 60 o 90 dias).
+        if !matches!(cycle_duration_days, 30 | 60 | 90) {
+            return Err(TokenizationError::InvalidCycleDuration);
         }
-        if title.len() == 0 || title.len() > Self::MAX_TEXT_LEN {
-            panic!("invalid title length");
+        if category.is_empty() || category.len() > Self::MAX_TEXT_LEN {
+            return Err(TokenizationError::InvalidCategoryLength);
         }
+        if title.is_empty() || title.len() > Self::MAX_TEXT_LEN {
+            return Err(TokenizationError::InvalidTitleLength);
+        }
+        let limits = limits.unwrap_or(PurchaseLimits {
+            min_purchase: 0,
+            max_per_wallet: WalletCap::None,
+        });
+        Self::validate_purchase_limits(total_tokens, &limits)?;
 
         let id = env
             .storage()
@@ -83,7 +390,7 @@ impl TerraTokenization {
             .get::<DataKey, u64>(&DataKey::NextAssetId)
             .unwrap_or(1);
         if id == u64::MAX {
-            panic!("asset id overflow");
+            return Err(TokenizationError::Overflow);
         }
 
         let asset = Asset {
@@ -94,21 +401,39 @@ impl TerraTokenization {
             price_per_token,
             total_tokens,
             available_tokens: total_tokens,
+            circulating_supply: 0,
             active: true,
+            lifecycle_status: LifecycleStatus::Funding,
+            cycle_duration_days,
+            cycle_start_at: 0,
+            cycle_end_at: 0,
+            funding_goal: 0,
+            funding_deadline: 0,
+            min_purchase: limits.min_purchase,
+            max_per_wallet: limits.max_per_wallet,
         };
 
-        env.storage().persistent().set(&DataKey::Asset(id), &asset);
-        let next_id = id.checked_add(1).unwrap_or_else(|| panic!("next asset id overflow"));
+        Self::write_asset(&env, &asset);
+        Self::record_price_point(&env, &asset);
+        let next_id = id.checked_add(1).ok_or(TokenizationError::Overflow)?;
         env.storage().instance().set(&DataKey::NextAssetId, &next_id);
+        env.events()
+            .publish((symbol_short!("asset"), symbol_short!("created"), id), asset);
 
-        id
+        Ok(id)
     }
 
-    pub fn buy_tokens(env: Env, buyer: Address, asset_id: u64, quantity: i128) -> i128 {
+    pub fn buy_tokens(
+        env: Env,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+    ) -> Result<i128, TokenizationError> {
+        Self::extend_instance_ttl(&env);
         // Cuando existe marketplace configurado, se fuerza el flujo cross-contract
         // para que no se puedan saltar pagos/comisiones.
         if env.storage().instance().has(&DataKey::Marketplace) {
-            panic!("use marketplace contract");
+            return Err(TokenizationError::UseMarketplace);
         }
         buyer.require_auth();
         Self::apply_sale(env, asset_id, quantity, buyer)
@@ -120,36 +445,27 @@ impl TerraTokenization {
         buyer: Address,
         asset_id: u64,
         quantity: i128,
-    ) -> i128 {
-        let marketplace = env
-            .storage()
-            .instance()
-            .get::<DataKey, Address>(&DataKey::Marketplace)
-            .unwrap_or_else(|| panic!("marketplace not configured"));
-        marketplace.require_auth();
+    ) -> Result<i128, TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_marketplace(&env)?;
 
-        let total = Self::apply_sale(env.clone(), asset_id, quantity, buyer);
-        let asset = env
-            .storage()
-            .persistent()
-            .get::<DataKey, Asset>(&DataKey::Asset(asset_id))
-            .unwrap_or_else(|| panic!("asset not found"));
+        let total = Self::apply_sale(env.clone(), asset_id, quantity, buyer)?;
+        let asset = Self::get_asset(env, asset_id)?;
         if asset.seller != seller {
-            panic!("seller mismatch");
+            return Err(TokenizationError::SellerMismatch);
         }
-        total
+        Ok(total)
     }
 
-    pub fn get_asset(env: Env, asset_id: u64) -> Asset {
-        env.storage()
-            .persistent()
-            .get::<DataKey, Asset>(&DataKey::Asset(asset_id))
-            .unwrap_or_else(|| panic!("asset not found"))
+    pub fn get_asset(env: Env, asset_id: u64) -> Result<Asset, TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::read_asset(&env, asset_id).ok_or(TokenizationError::AssetNotFound)
     }
 
     pub fn list_assets(env: Env, from_id: u64, limit: u32) -> Map<u64, Asset> {
+        Self::extend_instance_ttl(&env);
         let mut out: Map<u64, Asset> = Map::new(&env);
-        let max = if limit > 50 { 50 } else { limit };
+        let max = if limit > Self::MAX_PAGE_SIZE { Self::MAX_PAGE_SIZE } else { limit };
 
         let next_id = env
             .storage()
@@ -161,11 +477,7 @@ impl TerraTokenization {
         let mut count: u32 = 0;
 
         while current < next_id && count < max {
-            if let Some(asset) = env
-                .storage()
-                .persistent()
-                .get::<DataKey, Asset>(&DataKey::Asset(current))
-            {
+            if let Some(asset) = Self::read_asset(&env, current) {
                 out.set(current, asset);
                 count += 1;
             }
@@ -175,63 +487,877 @@ impl TerraTokenization {
         out
     }
 
+    // Mantenimiento: renueva el TTL de un rango de activos sin tener que leerlos
+    // uno por uno desde fuera.
+    pub fn bump_assets(env: Env, from_id: u64, limit: u32) -> Result<u32, TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let admin = Self::get_admin(env.clone())?;
+        admin.require_auth();
+
+        let max = if limit > Self::MAX_PAGE_SIZE { Self::MAX_PAGE_SIZE } else { limit };
+        let next_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextAssetId)
+            .unwrap_or(1);
+
+        let mut current = from_id;
+        let mut bumped: u32 = 0;
+        while current < next_id && bumped < max {
+            if Self::read_asset(&env, current).is_some() {
+                bumped += 1;
+            }
+            current += 1;
+        }
+
+        Ok(bumped)
+    }
+
     pub fn get_buyer_balance(env: Env, asset_id: u64, buyer: Address) -> i128 {
-        let key = DataKey::Balance((asset_id, buyer));
-        env.storage().persistent().get::<DataKey, i128>(&key).unwrap_or(0)
+        Self::extend_instance_ttl(&env);
+        Self::read_balance(&env, asset_id, buyer)
     }
 
-    pub fn set_asset_active(env: Env, seller: Address, asset_id: u64, active: bool) {
-        seller.require_auth();
+    pub fn transfer(
+        env: Env,
+        from: Address,
+        to: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        from.require_auth();
+        Self::move_balance(env, asset_id, from, to, amount)
+    }
 
-        let mut asset = env
-            .storage()
-            .persistent()
-            .get::<DataKey, Asset>(&DataKey::Asset(asset_id))
-            .unwrap_or_else(|| panic!("asset not found"));
+    pub fn approve(
+        env: Env,
+        from: Address,
+        spender: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        from.require_auth();
+
+        if amount < 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+        if Self::read_asset(&env, asset_id).is_none() {
+            return Err(TokenizationError::AssetNotFound);
+        }
+
+        Self::write_allowance(&env, asset_id, from.clone(), spender.clone(), amount);
+        env.events()
+            .publish((symbol_short!("approve"), asset_id, from, spender), amount);
+        Ok(())
+    }
+
+    pub fn allowance(env: Env, asset_id: u64, from: Address, spender: Address) -> i128 {
+        Self::extend_instance_ttl(&env);
+        Self::read_allowance(&env, asset_id, from, spender)
+    }
+
+    pub fn transfer_from(
+        env: Env,
+        spender: Address,
+        from: Address,
+        to: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        spender.require_auth();
+
+        let allowed = Self::read_allowance(&env, asset_id, from.clone(), spender.clone());
+        if allowed < amount {
+            return Err(TokenizationError::InsufficientAllowance);
+        }
+        let remaining = allowed
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_allowance(&env, asset_id, from.clone(), spender, remaining);
+
+        Self::move_balance(env, asset_id, from, to, amount)
+    }
+
+    // Bloquea tokens del holder mientras tenga una orden de venta abierta en el
+    // marketplace. Siguen siendo del holder (cuentan para payouts) pero no se
+    // pueden transferir hasta que el marketplace los libere.
+    pub fn escrow_tokens(
+        env: Env,
+        holder: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_marketplace(&env)?;
+
+        if amount <= 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+        Self::ensure_not_paused(&env, asset_id)?;
+        let asset = Self::get_asset(env.clone(), asset_id)?;
+        if !asset.active {
+            return Err(TokenizationError::AssetNotActive);
+        }
+        if asset.lifecycle_status == LifecycleStatus::Settled {
+            return Err(TokenizationError::AssetSettled);
+        }
+        Self::ensure_not_in_funding_escrow(&asset)?;
+
+        let balance = Self::read_balance(&env, asset_id, holder.clone());
+        if balance < amount {
+            return Err(TokenizationError::InsufficientBalance);
+        }
+        let escrowed = Self::read_escrow(&env, asset_id, holder.clone());
+        let updated_balance = balance
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        let updated_escrow = escrowed
+            .checked_add(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_balance(&env, asset_id, holder.clone(), updated_balance);
+        Self::write_escrow(&env, asset_id, holder.clone(), updated_escrow);
+        env.events()
+            .publish((symbol_short!("escrow"), asset_id, holder), amount);
+        Ok(())
+    }
+
+    // Entrega tokens bloqueados: a un comprador cuando se llena una orden o de
+    // vuelta al holder cuando se cancela.
+    pub fn release_escrow(
+        env: Env,
+        holder: Address,
+        to: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_marketplace(&env)?;
+
+        if amount <= 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+        if to != holder {
+            Self::ensure_not_paused(&env, asset_id)?;
+            Self::ensure_eligible(&env, asset_id, to.clone())?;
+            let asset = Self::get_asset(env.clone(), asset_id)?;
+            if !asset.active {
+                return Err(TokenizationError::AssetNotActive);
+            }
+            if asset.lifecycle_status == LifecycleStatus::Settled {
+                return Err(TokenizationError::AssetSettled);
+            }
+            Self::ensure_within_wallet_cap(&env, &asset, to.clone(), amount)?;
+        }
 
+        let escrowed = Self::read_escrow(&env, asset_id, holder.clone());
+        if escrowed < amount {
+            return Err(TokenizationError::InsufficientEscrow);
+        }
+        let updated_escrow = escrowed
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        let updated_balance = Self::read_balance(&env, asset_id, to.clone())
+            .checked_add(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_escrow(&env, asset_id, holder.clone(), updated_escrow);
+        Self::write_balance(&env, asset_id, to.clone(), updated_balance);
+        env.events()
+            .publish((symbol_short!("release"), asset_id, holder, to), amount);
+        Ok(())
+    }
+
+    pub fn get_escrowed_balance(env: Env, asset_id: u64, holder: Address) -> i128 {
+        Self::extend_instance_ttl(&env);
+        Self::read_escrow(&env, asset_id, holder)
+    }
+
+    pub fn set_asset_active(
+        env: Env,
+        seller: Address,
+        asset_id: u64,
+        active: bool,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        seller.require_auth();
+
+        let mut asset = Self::get_asset(env.clone(), asset_id)?;
         if asset.seller != seller {
-            panic!("only seller can update asset");
+            return Err(TokenizationError::Unauthorized);
         }
 
         asset.active = active;
-        env.storage().persistent().set(&DataKey::Asset(asset_id), &asset);
+        Self::write_asset(&env, &asset);
+        env.events()
+            .publish((symbol_short!("asset"), symbol_short!("active"), asset_id), active);
+        Ok(())
     }
 
-    fn apply_sale(env: Env, asset_id: u64, quantity: i128, buyer: Address) -> i128 {
+    fn apply_sale(
+        env: Env,
+        asset_id: u64,
+        quantity: i128,
+        buyer: Address,
+    ) -> Result<i128, TokenizationError> {
         if quantity <= 0 {
-            panic!("quantity must be > 0");
+            return Err(TokenizationError::InvalidAmount);
         }
+        Self::ensure_not_paused(&env, asset_id)?;
+        Self::ensure_eligible(&env, asset_id, buyer.clone())?;
 
-        let mut asset = env
-            .storage()
-            .persistent()
-            .get::<DataKey, Asset>(&DataKey::Asset(asset_id))
-            .unwrap_or_else(|| panic!("asset not found"));
+        let mut asset = Self::get_asset(env.clone(), asset_id)?;
 
         if !asset.active {
-            panic!("asset not active");
+            return Err(TokenizationError::AssetNotActive);
+        }
+        if asset.lifecycle_status != LifecycleStatus::Funding {
+            return Err(TokenizationError::AssetNotInFunding);
         }
 
         if asset.available_tokens < quantity {
-            panic!("insufficient available tokens");
+            return Err(TokenizationError::InsufficientAvailableTokens);
+        }
+        if quantity < asset.min_purchase {
+            return Err(TokenizationError::BelowMinPurchase);
         }
+        Self::ensure_within_wallet_cap(&env, &asset, buyer.clone(), quantity)?;
+        let prev = Self::read_balance(&env, asset_id, buyer.clone());
 
         asset.available_tokens = asset
             .available_tokens
             .checked_sub(quantity)
-            .unwrap_or_else(|| panic!("available token underflow"));
-        env.storage().persistent().set(&DataKey::Asset(asset_id), &asset);
+            .ok_or(TokenizationError::Overflow)?;
+        asset.circulating_supply = asset
+            .circulating_supply
+            .checked_add(quantity)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_asset(&env, &asset);
 
-        let key = DataKey::Balance((asset_id, buyer.clone()));
-        let prev = env.storage().persistent().get::<DataKey, i128>(&key).unwrap_or(0);
         let updated_balance = prev
             .checked_add(quantity)
-            .unwrap_or_else(|| panic!("buyer balance overflow"));
-        env.storage().persistent().set(&key, &updated_balance);
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_balance(&env, asset_id, buyer.clone(), updated_balance);
 
-        asset
+        let total = asset
             .price_per_token
             .checked_mul(quantity)
-            .unwrap_or_else(|| panic!("cost overflow"))
+            .ok_or(TokenizationError::Overflow)?;
+        env.events()
+            .publish((symbol_short!("sale"), asset_id, buyer), (quantity, total));
+        Ok(total)
+    }
+
+    pub fn update_price(
+        env: Env,
+        caller: Address,
+        asset_id: u64,
+        new_price: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let mut asset = Self::editable_asset(&env, caller, asset_id)?;
+        if new_price <= 0 {
+            return Err(TokenizationError::InvalidAssetValues);
+        }
+
+        let old_price = asset.price_per_token;
+        asset.price_per_token = new_price;
+        Self::write_asset(&env, &asset);
+        Self::record_price_point(&env, &asset);
+        env.events().publish(
+            (symbol_short!("asset"), symbol_short!("price"), asset_id),
+            (old_price, new_price),
+        );
+        Ok(())
+    }
+
+    pub fn increase_supply(
+        env: Env,
+        caller: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let mut asset = Self::editable_asset(&env, caller, asset_id)?;
+        if amount <= 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+
+        asset.total_tokens = asset
+            .total_tokens
+            .checked_add(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        asset.available_tokens = asset
+            .available_tokens
+            .checked_add(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_asset(&env, &asset);
+        Self::record_price_point(&env, &asset);
+        env.events().publish(
+            (symbol_short!("asset"), symbol_short!("supply"), asset_id),
+            (asset.total_tokens, asset.available_tokens),
+        );
+        Ok(())
+    }
+
+    // Solo retira tokens sin vender ni reservados por subastas del marketplace;
+    // lo ya comprado no se toca.
+    pub fn decrease_supply(
+        env: Env,
+        caller: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let mut asset = Self::editable_asset(&env, caller, asset_id)?;
+        if amount <= 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+        let reserved = match Self::get_marketplace(env.clone()) {
+            Some(marketplace) => {
+                MarketplaceClient::new(&env, &marketplace).get_auction_reserved(&asset_id)
+            }
+            None => 0,
+        };
+        let unreserved = asset
+            .available_tokens
+            .checked_sub(reserved)
+            .ok_or(TokenizationError::Overflow)?;
+        if unreserved < amount {
+            return Err(TokenizationError::InsufficientAvailableTokens);
+        }
+
+        asset.total_tokens = asset
+            .total_tokens
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        asset.available_tokens = asset
+            .available_tokens
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        if asset.total_tokens <= 0 || asset.total_tokens < asset.funding_goal {
+            return Err(TokenizationError::InvalidAssetValues);
+        }
+        // Con menos emision el minimo o un tope en bps pueden dejar de ser validos.
+        Self::validate_purchase_limits(
+            asset.total_tokens,
+            &PurchaseLimits {
+                min_purchase: asset.min_purchase,
+                max_per_wallet: asset.max_per_wallet,
+            },
+        )?;
+        Self::write_asset(&env, &asset);
+        Self::record_price_point(&env, &asset);
+        env.events().publish(
+            (symbol_short!("asset"), symbol_short!("supply"), asset_id),
+            (asset.total_tokens, asset.available_tokens),
+        );
+        Ok(())
+    }
+
+    // Pagina del historial desde el indice `from`, en orden cronologico.
+    pub fn get_price_history(env: Env, asset_id: u64, from: u32, limit: u32) -> Vec<PricePoint> {
+        Self::extend_instance_ttl(&env);
+        let max = if limit > Self::MAX_PAGE_SIZE { Self::MAX_PAGE_SIZE } else { limit };
+        let len = Self::read_price_history_len(&env, asset_id);
+
+        let mut out = Vec::new(&env);
+        let mut index = from;
+        while index < len && out.len() < max {
+            let key = DataKey::PriceHistory((asset_id, index));
+            if let Some(point) = env.storage().persistent().get::<DataKey, PricePoint>(&key) {
+                Self::extend_persistent_ttl(&env, &key);
+                out.push_back(point);
+            }
+            index += 1;
+        }
+        out
+    }
+
+    pub fn get_price_history_len(env: Env, asset_id: u64) -> u32 {
+        Self::extend_instance_ttl(&env);
+        Self::read_price_history_len(&env, asset_id)
+    }
+
+    // Activa el modo escrow: el marketplace retiene los pagos hasta que se vendan
+    // `funding_goal` tokens o venza `funding_deadline`. Solo antes de la primera
+    // venta; `(0, 0)` vuelve a la venta directa.
+    pub fn set_funding_goal(
+        env: Env,
+        caller: Address,
+        asset_id: u64,
+        funding_goal: i128,
+        funding_deadline: u64,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let mut asset = Self::get_asset(env.clone(), asset_id)?;
+        Self::require_seller_or_admin(env.clone(), caller, asset.seller.clone())?;
+        if !env.storage().instance().has(&DataKey::Marketplace) {
+            return Err(TokenizationError::MarketplaceNotConfigured);
+        }
+
+        if asset.lifecycle_status != LifecycleStatus::Funding {
+            return Err(TokenizationError::AssetNotInFunding);
+        }
+        if asset.circulating_supply > 0 {
+            return Err(TokenizationError::FundingGoalLocked);
+        }
+        let disabled = funding_goal == 0 && funding_deadline == 0;
+        if !disabled
+            && (funding_goal <= 0
+                || funding_goal > asset.total_tokens
+                || funding_deadline <= env.ledger().timestamp())
+        {
+            return Err(TokenizationError::InvalidFundingGoal);
+        }
+
+        asset.funding_goal = funding_goal;
+        asset.funding_deadline = funding_deadline;
+        Self::write_asset(&env, &asset);
+        env.events().publish(
+            (symbol_short!("asset"), symbol_short!("goal"), asset_id),
+            (funding_goal, funding_deadline),
+        );
+        Ok(())
+    }
+
+    // Deshace una venta de una ronda fallida: el marketplace devuelve el pago y
+    // aca se quitan los tokens al comprador y vuelven al inventario.
+    pub fn revert_sale(
+        env: Env,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        Self::require_marketplace(&env)?;
+
+        if quantity <= 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+        let mut asset = Self::get_asset(env.clone(), asset_id)?;
+        if asset.lifecycle_status != LifecycleStatus::Funding {
+            return Err(TokenizationError::AssetNotInFunding);
+        }
+        let balance = Self::read_balance(&env, asset_id, buyer.clone());
+        if balance < quantity {
+            return Err(TokenizationError::InsufficientBalance);
+        }
+
+        asset.available_tokens = asset
+            .available_tokens
+            .checked_add(quantity)
+            .ok_or(TokenizationError::Overflow)?;
+        asset.circulating_supply = asset
+            .circulating_supply
+            .checked_sub(quantity)
+            .ok_or(TokenizationError::Overflow)?;
+        let updated_balance = balance
+            .checked_sub(quantity)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_asset(&env, &asset);
+        Self::write_balance(&env, asset_id, buyer.clone(), updated_balance);
+        env.events()
+            .publish((symbol_short!("unsale"), asset_id, buyer), quantity);
+        Ok(())
+    }
+
+    pub fn start_operation(
+        env: Env,
+        caller: Address,
+        asset_id: u64,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let mut asset = Self::get_asset(env.clone(), asset_id)?;
+        Self::require_seller_or_admin(env.clone(), caller, asset.seller.clone())?;
+
+        if asset.lifecycle_status != LifecycleStatus::Funding {
+            return Err(TokenizationError::AssetNotInFunding);
+        }
+        if asset.funding_deadline > 0 && asset.circulating_supply < asset.funding_goal {
+            return Err(TokenizationError::FundingGoalNotReached);
+        }
+
+        let now = env.ledger().timestamp();
+        let duration = u64::from(asset.cycle_duration_days)
+            .checked_mul(Self::SECONDS_PER_DAY)
+            .ok_or(TokenizationError::Overflow)?;
+        asset.lifecycle_status = LifecycleStatus::Operating;
+        asset.cycle_start_at = now;
+        asset.cycle_end_at = now
+            .checked_add(duration)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_asset(&env, &asset);
+        env.events().publish(
+            (symbol_short!("asset"), symbol_short!("operating"), asset_id),
+            (asset.cycle_start_at, asset.cycle_end_at),
+        );
+        Ok(())
+    }
+
+    pub fn settle_asset(env: Env, caller: Address, asset_id: u64) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        let mut asset = Self::get_asset(env.clone(), asset_id)?;
+        Self::require_seller_or_admin(env.clone(), caller, asset.seller.clone())?;
+
+        if asset.lifecycle_status != LifecycleStatus::Operating {
+            return Err(TokenizationError::AssetNotOperating);
+        }
+        if env.ledger().timestamp() < asset.cycle_end_at {
+            return Err(TokenizationError::CycleNotFinished);
+        }
+
+        asset.lifecycle_status = LifecycleStatus::Settled;
+        Self::write_asset(&env, &asset);
+        env.events().publish(
+            (symbol_short!("asset"), symbol_short!("settled"), asset_id),
+            env.ledger().timestamp(),
+        );
+        Ok(())
+    }
+
+    pub fn redeem(
+        env: Env,
+        holder: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        Self::extend_instance_ttl(&env);
+        // Con marketplace configurado el canje solo entra por el marketplace,
+        // que paga el payout final del holder antes de quemar sus tokens.
+        match env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::Marketplace)
+        {
+            Some(marketplace) => marketplace.require_auth(),
+            None => holder.require_auth(),
+        }
+
+        if amount <= 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+        Self::ensure_not_paused(&env, asset_id)?;
+
+        let mut asset = Self::get_asset(env.clone(), asset_id)?;
+        if asset.lifecycle_status != LifecycleStatus::Settled {
+            return Err(TokenizationError::AssetNotSettled);
+        }
+
+        let balance = Self::read_balance(&env, asset_id, holder.clone());
+        if balance < amount {
+            return Err(TokenizationError::InsufficientBalance);
+        }
+        let updated_balance = balance
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        Self::write_balance(&env, asset_id, holder.clone(), updated_balance);
+
+        asset.circulating_supply = asset
+            .circulating_supply
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+        // Sin tokens en circulacion el activo queda cerrado; un nuevo ciclo se
+        // publica como un activo nuevo.
+        if asset.circulating_supply == 0 {
+            asset.active = false;
+        }
+        Self::write_asset(&env, &asset);
+        env.events().publish(
+            (symbol_short!("redeem"), asset_id, holder),
+            (amount, asset.circulating_supply),
+        );
+        Ok(())
+    }
+
+    fn extend_instance_ttl(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(Self::INSTANCE_LIFETIME_THRESHOLD, Self::INSTANCE_BUMP_AMOUNT);
+    }
+
+    fn extend_persistent_ttl(env: &Env, key: &DataKey) {
+        env.storage().persistent().extend_ttl(
+            key,
+            Self::PERSISTENT_LIFETIME_THRESHOLD,
+            Self::PERSISTENT_BUMP_AMOUNT,
+        );
+    }
+
+    fn read_asset(env: &Env, asset_id: u64) -> Option<Asset> {
+        let key = DataKey::Asset(asset_id);
+        let asset = env.storage().persistent().get::<DataKey, Asset>(&key);
+        if asset.is_some() {
+            Self::extend_persistent_ttl(env, &key);
+        }
+        asset
+    }
+
+    fn write_asset(env: &Env, asset: &Asset) {
+        let key = DataKey::Asset(asset.id);
+        env.storage().persistent().set(&key, asset);
+        Self::extend_persistent_ttl(env, &key);
+    }
+
+    fn read_balance(env: &Env, asset_id: u64, holder: Address) -> i128 {
+        let key = DataKey::Balance((asset_id, holder));
+        match env.storage().persistent().get::<DataKey, i128>(&key) {
+            Some(balance) => {
+                Self::extend_persistent_ttl(env, &key);
+                balance
+            }
+            None => 0,
+        }
+    }
+
+    fn write_balance(env: &Env, asset_id: u64, holder: Address, balance: i128) {
+        let key = DataKey::Balance((asset_id, holder));
+        if balance == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &balance);
+            Self::extend_persistent_ttl(env, &key);
+        }
+    }
+
+    fn read_price_history_len(env: &Env, asset_id: u64) -> u32 {
+        let key = DataKey::PriceHistoryLen(asset_id);
+        match env.storage().persistent().get::<DataKey, u32>(&key) {
+            Some(len) => {
+                Self::extend_persistent_ttl(env, &key);
+                len
+            }
+            None => 0,
+        }
+    }
+
+    fn record_price_point(env: &Env, asset: &Asset) {
+        let len = Self::read_price_history_len(env, asset.id);
+        let point = PricePoint {
+            timestamp: env.ledger().timestamp(),
+            price_per_token: asset.price_per_token,
+            total_tokens: asset.total_tokens,
+        };
+        let key = DataKey::PriceHistory((asset.id, len));
+        env.storage().persistent().set(&key, &point);
+        Self::extend_persistent_ttl(env, &key);
+        let len_key = DataKey::PriceHistoryLen(asset.id);
+        env.storage().persistent().set(&len_key, &(len + 1));
+        Self::extend_persistent_ttl(env, &len_key);
+    }
+
+    fn read_escrow(env: &Env, asset_id: u64, holder: Address) -> i128 {
+        let key = DataKey::Escrow((asset_id, holder));
+        match env.storage().persistent().get::<DataKey, i128>(&key) {
+            Some(amount) => {
+                Self::extend_persistent_ttl(env, &key);
+                amount
+            }
+            None => 0,
+        }
+    }
+
+    fn write_escrow(env: &Env, asset_id: u64, holder: Address, amount: i128) {
+        let key = DataKey::Escrow((asset_id, holder));
+        if amount == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &amount);
+            Self::extend_persistent_ttl(env, &key);
+        }
+    }
+
+    fn read_allowance(env: &Env, asset_id: u64, from: Address, spender: Address) -> i128 {
+        let key = DataKey::Allowance((asset_id, from, spender));
+        match env.storage().persistent().get::<DataKey, i128>(&key) {
+            Some(amount) => {
+                Self::extend_persistent_ttl(env, &key);
+                amount
+            }
+            None => 0,
+        }
+    }
+
+    fn write_allowance(env: &Env, asset_id: u64, from: Address, spender: Address, amount: i128) {
+        let key = DataKey::Allowance((asset_id, from, spender));
+        if amount == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &amount);
+            Self::extend_persistent_ttl(env, &key);
+        }
+    }
+
+    // Mientras la ronda con escrow esta abierta los tokens no se mueven: si
+    // fracasa, cada comprador tiene que poder devolver exactamente lo comprado.
+    fn ensure_not_in_funding_escrow(asset: &Asset) -> Result<(), TokenizationError> {
+        if asset.funding_deadline > 0 && asset.lifecycle_status == LifecycleStatus::Funding {
+            return Err(TokenizationError::FundingInProgress);
+        }
+        Ok(())
+    }
+
+    fn validate_purchase_limits(total_tokens: i128, limits: &PurchaseLimits) -> Result<(), TokenizationError> {
+        let min_purchase = limits.min_purchase;
+        let valid_cap = match limits.max_per_wallet {
+            WalletCap::None => true,
+            WalletCap::Tokens(tokens) => tokens > 0,
+            WalletCap::Bps(bps) => bps > 0 && bps <= 10_000,
+        };
+        if !valid_cap || min_purchase < 0 || min_purchase > total_tokens {
+            return Err(TokenizationError::InvalidPurchaseLimits);
+        }
+        // Un tope por debajo del minimo impediria cualquier compra.
+        if let Some(limit) = limits.max_per_wallet.limit(total_tokens) {
+            if limit < min_purchase.max(1) {
+                return Err(TokenizationError::InvalidPurchaseLimits);
+            }
+        }
+        Ok(())
+    }
+
+    // Los tokens en escrow siguen siendo del holder y cuentan para el tope.
+    fn ensure_within_wallet_cap(
+        env: &Env,
+        asset: &Asset,
+        holder: Address,
+        incoming: i128,
+    ) -> Result<(), TokenizationError> {
+        let Some(limit) = asset.max_per_wallet.limit(asset.total_tokens) else {
+            return Ok(());
+        };
+        let held = Self::read_balance(env, asset.id, holder.clone())
+            .checked_add(Self::read_escrow(env, asset.id, holder))
+            .and_then(|held| held.checked_add(incoming))
+            .ok_or(TokenizationError::Overflow)?;
+        if held > limit {
+            return Err(TokenizationError::WalletCapExceeded);
+        }
+        Ok(())
+    }
+
+    fn require_marketplace(env: &Env) -> Result<(), TokenizationError> {
+        let marketplace = env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::Marketplace)
+            .ok_or(TokenizationError::MarketplaceNotConfigured)?;
+        marketplace.require_auth();
+        Ok(())
+    }
+
+    fn require_role(env: Env, role: Role) -> Result<(), TokenizationError> {
+        let holder = match Self::get_role(env.clone(), role) {
+            Some(holder) => holder,
+            None => Self::get_admin(env)?,
+        };
+        holder.require_auth();
+        Ok(())
+    }
+
+    fn ensure_not_paused(env: &Env, asset_id: u64) -> Result<(), TokenizationError> {
+        if Self::is_paused(env.clone()) || Self::is_asset_paused(env.clone(), asset_id) {
+            return Err(TokenizationError::Paused);
+        }
+        Ok(())
+    }
+
+    fn is_compliance_required(env: &Env, asset_id: u64) -> bool {
+        if env
+            .storage()
+            .instance()
+            .get::<DataKey, bool>(&DataKey::ComplianceRequired)
+            .unwrap_or(false)
+        {
+            return true;
+        }
+        let key = DataKey::AssetComplianceRequired(asset_id);
+        if env.storage().persistent().has(&key) {
+            Self::extend_persistent_ttl(env, &key);
+            return true;
+        }
+        false
+    }
+
+    fn ensure_eligible(env: &Env, asset_id: u64, investor: Address) -> Result<(), TokenizationError> {
+        if !Self::is_eligible(env.clone(), asset_id, investor) {
+            return Err(TokenizationError::InvestorNotVerified);
+        }
+        Ok(())
+    }
+
+    // Precio y emision solo se editan durante FUNDING, por el vendedor o el admin.
+    fn editable_asset(env: &Env, caller: Address, asset_id: u64) -> Result<Asset, TokenizationError> {
+        let asset = Self::get_asset(env.clone(), asset_id)?;
+        Self::require_seller_or_admin(env.clone(), caller, asset.seller.clone())?;
+        Self::ensure_not_paused(env, asset_id)?;
+        if asset.lifecycle_status != LifecycleStatus::Funding {
+            return Err(TokenizationError::AssetNotInFunding);
+        }
+        Ok(asset)
+    }
+
+    fn require_seller_or_admin(
+        env: Env,
+        caller: Address,
+        seller: Address,
+    ) -> Result<(), TokenizationError> {
+        caller.require_auth();
+        let admin = Self::get_admin(env)?;
+        if caller != seller && caller != admin {
+            return Err(TokenizationError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    fn move_balance(
+        env: Env,
+        asset_id: u64,
+        from: Address,
+        to: Address,
+        amount: i128,
+    ) -> Result<(), TokenizationError> {
+        if amount <= 0 {
+            return Err(TokenizationError::InvalidAmount);
+        }
+        if from == to {
+            return Err(TokenizationError::SelfTransfer);
+        }
+        Self::ensure_not_paused(&env, asset_id)?;
+        Self::ensure_eligible(&env, asset_id, to.clone())?;
+
+        let asset = Self::get_asset(env.clone(), asset_id)?;
+        if !asset.active {
+            return Err(TokenizationError::AssetNotActive);
+        }
+        // Tras la liquidacion los balances quedan congelados para que el reparto
+        // de pagos del marketplace sea proporcional a una foto estable.
+        if asset.lifecycle_status == LifecycleStatus::Settled {
+            return Err(TokenizationError::AssetSettled);
+        }
+        Self::ensure_not_in_funding_escrow(&asset)?;
+        Self::ensure_within_wallet_cap(&env, &asset, to.clone(), amount)?;
+
+        let from_balance = Self::read_balance(&env, asset_id, from.clone());
+        if from_balance < amount {
+            return Err(TokenizationError::InsufficientBalance);
+        }
+        let from_updated = from_balance
+            .checked_sub(amount)
+            .ok_or(TokenizationError::Overflow)?;
+
+        let to_balance = Self::read_balance(&env, asset_id, to.clone());
+        let to_updated = to_balance
+            .checked_add(amount)
+            .ok_or(TokenizationError::Overflow)?;
+
+        Self::write_balance(&env, asset_id, from.clone(), from_updated);
+        Self::write_balance(&env, asset_id, to.clone(), to_updated);
+        env.events()
+            .publish((symbol_short!("transfer"), asset_id, from, to), amount);
+        Ok(())
     }
 }
+
+mod test;
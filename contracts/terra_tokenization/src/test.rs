@@ -0,0 +1,443 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::storage::Persistent as _;
+use soroban_sdk::testutils::{Address as _, Ledger, MockAuth, MockAuthInvoke};
+use soroban_sdk::IntoVal;
+
+fn setup(env: &Env) -> (TerraTokenizationClient<'_>, Address, u64) {
+    env.mock_all_auths();
+
+    let contract_id = env.register(TerraTokenization, ());
+    let client = TerraTokenizationClient::new(env, &contract_id);
+    let admin = Address::generate(env);
+    let seller = Address::generate(env);
+    client.init(&admin);
+
+    let asset_id = client.create_asset(
+        &seller,
+        &String::from_str(env, "cultivo"),
+        &String::from_str(env, "Soja lote 4"),
+        &100,
+        &1_000,
+        &30,
+        &None,
+    );
+    (client, contract_id, asset_id)
+}
+
+fn advance_days(env: &Env, days: u32) {
+    env.ledger().with_mut(|ledger| {
+        ledger.sequence_number += days * TerraTokenization::DAY_IN_LEDGERS;
+        ledger.timestamp += u64::from(days) * TerraTokenization::SECONDS_PER_DAY;
+    });
+}
+
+fn asset_ttl(env: &Env, contract_id: &Address, asset_id: u64) -> u32 {
+    env.as_contract(contract_id, || {
+        env.storage().persistent().get_ttl(&DataKey::Asset(asset_id))
+    })
+}
+
+#[test]
+fn asset_and_balance_survive_while_in_use() {
+    let env = Env::default();
+    let (client, _, asset_id) = setup(&env);
+    let buyer = Address::generate(&env);
+    client.buy_tokens(&buyer, &asset_id, &10);
+
+    // 120 dias en total: el doble del TTL persistente, renovado en cada lectura.
+    for _ in 0..6 {
+        advance_days(&env, 20);
+        assert_eq!(client.get_asset(&asset_id).available_tokens, 990);
+        assert_eq!(client.get_buyer_balance(&asset_id, &buyer), 10);
+    }
+}
+
+#[test]
+fn bump_assets_renews_asset_ttl() {
+    let env = Env::default();
+    let (client, contract_id, asset_id) = setup(&env);
+
+    advance_days(&env, 25);
+    assert!(asset_ttl(&env, &contract_id, asset_id) < TerraTokenization::PERSISTENT_LIFETIME_THRESHOLD);
+    assert_eq!(client.bump_assets(&1, &10), 1);
+    assert_eq!(
+        asset_ttl(&env, &contract_id, asset_id),
+        TerraTokenization::PERSISTENT_BUMP_AMOUNT
+    );
+
+    advance_days(&env, 25);
+    client.bump_assets(&1, &10);
+    advance_days(&env, 25);
+    assert_eq!(client.get_asset(&asset_id).id, asset_id);
+}
+
+#[test]
+#[should_panic(expected = "archived")]
+fn untouched_asset_is_archived() {
+    let env = Env::default();
+    let (client, _, asset_id) = setup(&env);
+
+    // Solo se mantiene viva la instancia; el activo nunca se toca.
+    for _ in 0..4 {
+        advance_days(&env, 20);
+        client.get_marketplace();
+    }
+    client.get_asset(&asset_id);
+}
+
+#[test]
+fn price_and_supply_edits_are_recorded() {
+    let env = Env::default();
+    let (client, _, asset_id) = setup(&env);
+    let seller = client.get_asset(&asset_id).seller;
+    client.buy_tokens(&Address::generate(&env), &asset_id, &100);
+
+    advance_days(&env, 1);
+    client.update_price(&seller, &asset_id, &120);
+    client.decrease_supply(&seller, &asset_id, &400);
+    assert_eq!(
+        client.try_decrease_supply(&seller, &asset_id, &600),
+        Err(Ok(TokenizationError::InsufficientAvailableTokens))
+    );
+
+    let asset = client.get_asset(&asset_id);
+    assert_eq!((asset.total_tokens, asset.available_tokens), (600, 500));
+
+    assert_eq!(client.get_price_history_len(&asset_id), 3);
+    let history = client.get_price_history(&asset_id, &0, &10);
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.get_unchecked(0).price_per_token, 100);
+    let page = client.get_price_history(&asset_id, &2, &10);
+    assert_eq!(page.len(), 1);
+    let last = page.get_unchecked(0);
+    assert_eq!((last.price_per_token, last.total_tokens), (120, 600));
+    assert_eq!(last.timestamp, TerraTokenization::SECONDS_PER_DAY);
+}
+
+#[test]
+fn purchase_limits_are_enforced() {
+    let env = Env::default();
+    let (client, _, _) = setup(&env);
+    let seller = Address::generate(&env);
+    let limits = PurchaseLimits {
+        min_purchase: 10,
+        max_per_wallet: WalletCap::Bps(500),
+    };
+    let asset_id = client.create_asset(
+        &seller,
+        &String::from_str(&env, "tierra"),
+        &String::from_str(&env, "Campo norte"),
+        &100,
+        &1_000,
+        &60,
+        &Some(limits),
+    );
+
+    let buyer = Address::generate(&env);
+    assert_eq!(
+        client.try_buy_tokens(&buyer, &asset_id, &5),
+        Err(Ok(TokenizationError::BelowMinPurchase))
+    );
+    client.buy_tokens(&buyer, &asset_id, &40);
+    assert_eq!(
+        client.try_buy_tokens(&buyer, &asset_id, &20),
+        Err(Ok(TokenizationError::WalletCapExceeded))
+    );
+    client.buy_tokens(&buyer, &asset_id, &10);
+    assert_eq!(client.get_buyer_balance(&asset_id, &buyer), 50);
+
+    let over_cap = PurchaseLimits {
+        min_purchase: 100,
+        max_per_wallet: WalletCap::Tokens(50),
+    };
+    assert_eq!(
+        client.try_create_asset(
+            &seller,
+            &String::from_str(&env, "tierra"),
+            &String::from_str(&env, "Campo sur"),
+            &100,
+            &1_000,
+            &60,
+            &Some(over_cap),
+        ),
+        Err(Ok(TokenizationError::InvalidPurchaseLimits))
+    );
+}
+
+#[test]
+fn wallet_cap_applies_to_every_incoming_transfer() {
+    let env = Env::default();
+    let (client, _, _) = setup(&env);
+    let seller = Address::generate(&env);
+    let limits = PurchaseLimits {
+        min_purchase: 10,
+        max_per_wallet: WalletCap::Bps(500),
+    };
+    let asset_id = client.create_asset(
+        &seller,
+        &String::from_str(&env, "tierra"),
+        &String::from_str(&env, "Campo norte"),
+        &100,
+        &1_000,
+        &60,
+        &Some(limits),
+    );
+    let (a, b, c) = (Address::generate(&env), Address::generate(&env), Address::generate(&env));
+    client.buy_tokens(&a, &asset_id, &50);
+    client.buy_tokens(&b, &asset_id, &40);
+    client.buy_tokens(&c, &asset_id, &30);
+
+    assert_eq!(
+        client.try_transfer(&a, &b, &asset_id, &20),
+        Err(Ok(TokenizationError::WalletCapExceeded))
+    );
+    client.transfer(&a, &b, &asset_id, &10);
+    assert_eq!(client.get_buyer_balance(&asset_id, &b), 50);
+
+    client.set_marketplace(&Address::generate(&env));
+    client.escrow_tokens(&a, &asset_id, &40);
+    assert_eq!(
+        client.try_release_escrow(&a, &b, &asset_id, &10),
+        Err(Ok(TokenizationError::WalletCapExceeded))
+    );
+    client.release_escrow(&a, &c, &asset_id, &20);
+    client.release_escrow(&a, &a, &asset_id, &20);
+    assert_eq!(client.get_buyer_balance(&asset_id, &c), 50);
+    assert_eq!(client.get_buyer_balance(&asset_id, &a), 20);
+}
+
+#[test]
+fn decrease_supply_keeps_purchase_limits_valid() {
+    let env = Env::default();
+    let (client, _, _) = setup(&env);
+    let seller = Address::generate(&env);
+    let limits = PurchaseLimits {
+        min_purchase: 10,
+        max_per_wallet: WalletCap::Bps(500),
+    };
+    let asset_id = client.create_asset(
+        &seller,
+        &String::from_str(&env, "tierra"),
+        &String::from_str(&env, "Campo norte"),
+        &100,
+        &1_000,
+        &60,
+        &Some(limits),
+    );
+    client.buy_tokens(&Address::generate(&env), &asset_id, &50);
+
+    // 5% de 150 tokens quedaria por debajo del minimo de 10.
+    assert_eq!(
+        client.try_decrease_supply(&seller, &asset_id, &850),
+        Err(Ok(TokenizationError::InvalidPurchaseLimits))
+    );
+    client.decrease_supply(&seller, &asset_id, &800);
+    assert_eq!(client.get_asset(&asset_id).total_tokens, 200);
+}
+
+#[test]
+fn transfer_from_spends_the_allowance() {
+    let env = Env::default();
+    let (client, _, _) = setup(&env);
+    let seller = Address::generate(&env);
+    let limits = PurchaseLimits {
+        min_purchase: 0,
+        max_per_wallet: WalletCap::Tokens(100),
+    };
+    let asset_id = client.create_asset(
+        &seller,
+        &String::from_str(&env, "tierra"),
+        &String::from_str(&env, "Campo norte"),
+        &100,
+        &1_000,
+        &60,
+        &Some(limits),
+    );
+    let owner = Address::generate(&env);
+    let (spender, to) = (Address::generate(&env), Address::generate(&env));
+    client.buy_tokens(&owner, &asset_id, &100);
+    client.buy_tokens(&to, &asset_id, &90);
+
+    client.approve(&owner, &spender, &asset_id, &30);
+    client.transfer_from(&spender, &owner, &to, &asset_id, &10);
+    assert_eq!(client.allowance(&asset_id, &owner, &spender), 20);
+    assert_eq!(client.get_buyer_balance(&asset_id, &to), 100);
+    assert_eq!(
+        client.try_transfer_from(&spender, &owner, &spender, &asset_id, &21),
+        Err(Ok(TokenizationError::InsufficientAllowance))
+    );
+    assert_eq!(
+        client.try_transfer_from(&spender, &owner, &owner, &asset_id, &5),
+        Err(Ok(TokenizationError::SelfTransfer))
+    );
+    // `to` ya esta en el tope de 100 tokens.
+    assert_eq!(
+        client.try_transfer_from(&spender, &owner, &to, &asset_id, &1),
+        Err(Ok(TokenizationError::WalletCapExceeded))
+    );
+
+    client.set_compliance_required(&true);
+    assert_eq!(
+        client.try_transfer_from(&spender, &owner, &spender, &asset_id, &5),
+        Err(Ok(TokenizationError::InvestorNotVerified))
+    );
+    let expires_at = 30 * TerraTokenization::SECONDS_PER_DAY;
+    client.set_verification(&spender, &symbol_short!("AR"), &expires_at);
+    client.transfer_from(&spender, &owner, &spender, &asset_id, &5);
+    assert_eq!(client.allowance(&asset_id, &owner, &spender), 15);
+    assert_eq!(client.get_buyer_balance(&asset_id, &owner), 85);
+
+    // Un rechazo no consume allowance y `approve` con 0 la revoca.
+    client.approve(&owner, &spender, &asset_id, &0);
+    assert_eq!(
+        client.try_transfer_from(&spender, &owner, &spender, &asset_id, &1),
+        Err(Ok(TokenizationError::InsufficientAllowance))
+    );
+}
+
+#[test]
+fn admin_handover_and_roles_need_the_right_signer() {
+    let env = Env::default();
+    let (client, contract_id, _) = setup(&env);
+    let admin = client.get_admin();
+    let (next, pauser) = (Address::generate(&env), Address::generate(&env));
+
+    assert_eq!(client.try_accept_admin(), Err(Ok(TokenizationError::NoPendingAdmin)));
+    client.propose_admin(&next);
+    assert_eq!(client.get_admin(), admin);
+    client.accept_admin();
+    assert_eq!(env.auths()[0].0, next);
+    assert_eq!((client.get_admin(), client.get_pending_admin()), (next.clone(), None));
+    assert_eq!(client.try_accept_admin(), Err(Ok(TokenizationError::NoPendingAdmin)));
+
+    // Sin titular el rol lo ejerce el admin; con titular, solo el titular.
+    client.set_paused(&true);
+    assert_eq!(env.auths()[0].0, next);
+    client.set_role(&Role::Pauser, &Some(pauser.clone()));
+    client.set_paused(&false);
+    assert_eq!(env.auths()[0].0, pauser);
+    let as_admin = MockAuth {
+        address: &next,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "set_paused",
+            args: (true,).into_val(&env),
+            sub_invokes: &[],
+        },
+    };
+    assert!(client.mock_auths(&[as_admin]).try_set_paused(&true).is_err());
+    assert!(!client.is_paused());
+}
+
+#[test]
+fn pauses_block_sales_and_transfers() {
+    let env = Env::default();
+    let (client, _, asset_id) = setup(&env);
+    let other = client.create_asset(
+        &Address::generate(&env),
+        &String::from_str(&env, "tierra"),
+        &String::from_str(&env, "Campo norte"),
+        &100,
+        &1_000,
+        &60,
+        &None,
+    );
+    let (a, b) = (Address::generate(&env), Address::generate(&env));
+    client.buy_tokens(&a, &asset_id, &10);
+
+    client.set_paused(&true);
+    assert_eq!(client.try_buy_tokens(&a, &other, &10), Err(Ok(TokenizationError::Paused)));
+    assert_eq!(client.try_transfer(&a, &b, &asset_id, &5), Err(Ok(TokenizationError::Paused)));
+    client.set_paused(&false);
+
+    // La pausa por activo no toca a los demas.
+    client.set_asset_paused(&asset_id, &true);
+    assert!(client.is_asset_paused(&asset_id));
+    assert_eq!(client.try_buy_tokens(&a, &asset_id, &10), Err(Ok(TokenizationError::Paused)));
+    assert_eq!(client.try_transfer(&a, &b, &asset_id, &5), Err(Ok(TokenizationError::Paused)));
+    client.buy_tokens(&a, &other, &10);
+    client.set_asset_paused(&asset_id, &false);
+    client.transfer(&a, &b, &asset_id, &5);
+    assert_eq!(client.get_buyer_balance(&asset_id, &b), 5);
+}
+
+#[test]
+fn kyc_gates_purchases_and_transfers() {
+    let env = Env::default();
+    let (client, _, asset_id) = setup(&env);
+    let other = client.create_asset(
+        &Address::generate(&env),
+        &String::from_str(&env, "tierra"),
+        &String::from_str(&env, "Campo norte"),
+        &100,
+        &1_000,
+        &60,
+        &None,
+    );
+    let (a, b) = (Address::generate(&env), Address::generate(&env));
+    client.buy_tokens(&a, &asset_id, &20);
+
+    // Requisito puntual: solo el activo marcado exige verificacion.
+    client.set_asset_compliance_required(&asset_id, &true);
+    assert!(!client.is_eligible(&asset_id, &b) && client.is_eligible(&other, &b));
+    assert_eq!(
+        client.try_buy_tokens(&b, &asset_id, &10),
+        Err(Ok(TokenizationError::InvestorNotVerified))
+    );
+    assert_eq!(
+        client.try_transfer(&a, &b, &asset_id, &5),
+        Err(Ok(TokenizationError::InvestorNotVerified))
+    );
+    client.buy_tokens(&b, &other, &10);
+    client.set_asset_compliance_required(&asset_id, &false);
+
+    // Requisito global: alcanza a todos los activos hasta que venza la verificacion.
+    client.set_compliance_required(&true);
+    assert_eq!(
+        client.try_buy_tokens(&b, &other, &10),
+        Err(Ok(TokenizationError::InvestorNotVerified))
+    );
+    assert_eq!(
+        client.try_set_verification(&b, &symbol_short!("AR"), &0),
+        Err(Ok(TokenizationError::InvalidVerification))
+    );
+    client.set_verification(&b, &symbol_short!("AR"), &TerraTokenization::SECONDS_PER_DAY);
+    client.buy_tokens(&b, &other, &10);
+    client.transfer(&a, &b, &asset_id, &5);
+
+    advance_days(&env, 1);
+    assert!(!client.is_verified(&b));
+    assert_eq!(
+        client.try_transfer(&a, &b, &asset_id, &5),
+        Err(Ok(TokenizationError::InvestorNotVerified))
+    );
+    client.set_verification(&b, &symbol_short!("AR"), &(2 * TerraTokenization::SECONDS_PER_DAY));
+    client.transfer(&a, &b, &asset_id, &5);
+    client.revoke_verification(&b);
+    assert_eq!(
+        client.try_buy_tokens(&b, &asset_id, &10),
+        Err(Ok(TokenizationError::InvestorNotVerified))
+    );
+    assert_eq!(client.get_buyer_balance(&asset_id, &b), 10);
+}
+
+#[test]
+fn price_history_pages_past_the_page_size() {
+    let env = Env::default();
+    let (client, _, asset_id) = setup(&env);
+    let seller = client.get_asset(&asset_id).seller;
+    for price in 101..161 {
+        client.update_price(&seller, &asset_id, &price);
+    }
+
+    // 61 puntos: la creacion mas 60 cambios, leidos de a 50 como maximo.
+    assert_eq!(client.get_price_history_len(&asset_id), 61);
+    let first = client.get_price_history(&asset_id, &0, &100);
+    assert_eq!(first.len(), 50);
+    let rest = client.get_price_history(&asset_id, &50, &50);
+    assert_eq!(rest.len(), 11);
+    assert_eq!(rest.get_unchecked(10).price_per_token, 160);
+    assert_eq!(client.get_price_history(&asset_id, &61, &50).len(), 0);
+}
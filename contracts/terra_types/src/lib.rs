@@ -0,0 +1,292 @@
+#![no_std]
+
+// Tipos compartidos entre los contratos y el backend off-chain. Un cambio en
+// `Asset` o `PurchaseReceipt` rompe la decodificacion cross-contract, por eso
+// existe una sola definicion.
+
+use soroban_sdk::{contractclient, contracterror, contracttype, Address, Env, String};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum LifecycleStatus {
+    Funding,
+    Operating,
+    Settled,
+}
+
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct Asset {
+    pub id: u64,
+    pub seller: Address,
+    pub category: String,
+    pub title: String,
+    pub price_per_token: i128,
+    pub total_tokens: i128,
+    pub available_tokens: i128,
+    pub circulating_supply: i128,
+    pub active: bool,
+    pub lifecycle_status: LifecycleStatus,
+    pub cycle_duration_days: u32,
+    pub cycle_start_at: u64,
+    pub cycle_end_at: u64,
+    // Ronda con escrow: `funding_deadline = 0` significa venta directa sin meta.
+    pub funding_goal: i128,
+    pub funding_deadline: u64,
+    // Limites por comprador en ventas primarias; `min_purchase = 0` es sin minimo.
+    pub min_purchase: i128,
+    pub max_per_wallet: WalletCap,
+}
+
+// Tope de tenencia por billetera: tokens fijos o bps de `total_tokens`, asi el
+// tope en bps acompana los cambios de emision.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum WalletCap {
+    None,
+    Tokens(i128),
+    Bps(u32),
+}
+
+// Parametro opcional de `create_asset`; sin limites equivale a
+// `{ min_purchase: 0, max_per_wallet: WalletCap::None }`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PurchaseLimits {
+    pub min_purchase: i128,
+    pub max_per_wallet: WalletCap,
+}
+
+impl WalletCap {
+    pub fn limit(&self, total_tokens: i128) -> Option<i128> {
+        match *self {
+            WalletCap::None => None,
+            WalletCap::Tokens(tokens) => Some(tokens),
+            WalletCap::Bps(bps) => Some(total_tokens.saturating_mul(i128::from(bps)) / 10_000),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct PurchaseReceipt {
+    pub asset_id: u64,
+    pub seller: Address,
+    pub buyer: Address,
+    pub quantity: i128,
+    pub total_paid: i128,
+    pub fee_paid: i128,
+    pub seller_amount: i128,
+    pub referrer: Option<Address>,
+    // Parte del fee (ya incluida en `fee_paid`) que cobra el referido.
+    pub referral_amount: i128,
+    // Token en que estan expresados los montos anteriores.
+    pub payment_token: Address,
+    // Total en el token base antes de convertir; es lo que suma al volumen.
+    pub quoted_total: i128,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PriceData {
+    pub value: i128,
+    pub timestamp: u64,
+}
+
+// Mediana de los reportes vigentes. `timestamp` es el del reporte mas viejo
+// usado, asi la antiguedad nunca se subestima.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeedData {
+    pub price: i128,
+    pub yield_bps: i128,
+    pub timestamp: u64,
+    pub sources: u32,
+}
+
+// Codigos estables: el frontend y el backend off-chain los mapean a mensajes.
+// Nunca reutilizar ni renumerar un codigo existente.
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum TokenizationError {
+    AlreadyInitialized = 1,
+    NotInitialized = 2,
+    Unauthorized = 3,
+    InvalidAmount = 4,
+    InvalidAssetValues = 5,
+    InvalidCycleDuration = 6,
+    InvalidCategoryLength = 7,
+    InvalidTitleLength = 8,
+    AssetNotFound = 9,
+    AssetNotActive = 10,
+    AssetNotInFunding = 11,
+    AssetNotOperating = 12,
+    AssetNotSettled = 13,
+    AssetSettled = 14,
+    CycleNotFinished = 15,
+    InsufficientAvailableTokens = 16,
+    InsufficientBalance = 17,
+    InsufficientAllowance = 18,
+    SelfTransfer = 19,
+    MarketplaceNotConfigured = 20,
+    UseMarketplace = 21,
+    SellerMismatch = 22,
+    Overflow = 23,
+    AlreadyMigrated = 24,
+    NoPendingAdmin = 25,
+    Paused = 26,
+    InvestorNotVerified = 27,
+    InvalidVerification = 28,
+    InsufficientEscrow = 29,
+    InvalidFundingGoal = 30,
+    FundingGoalLocked = 31,
+    FundingGoalNotReached = 32,
+    FundingInProgress = 33,
+    BelowMinPurchase = 34,
+    WalletCapExceeded = 35,
+    InvalidPurchaseLimits = 36,
+}
+
+// Codigos estables y en un rango distinto al de tokenizacion (1..) para que un
+// error propagado desde la llamada cross-contract no se confunda con uno propio.
+// Tiene las 50 variantes que admite Soroban: los codigos nuevos del rango van en
+// otro enum (ver `AuctionError`).
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum MarketplaceError {
+    AlreadyInitialized = 100,
+    NotInitialized = 101,
+    Unauthorized = 102,
+    InvalidAmount = 103,
+    InvalidFeeBps = 104,
+    InvalidLiquidityBps = 105,
+    UnsupportedNetwork = 106,
+    AssetNotActive = 107,
+    AssetNotInFunding = 108,
+    AssetNotSettled = 109,
+    InsufficientAvailableTokens = 110,
+    TokenizationContractMissing = 111,
+    PaymentTokenMissing = 112,
+    TreasuryMissing = 113,
+    NoTokensInCirculation = 114,
+    NoPayoutDeposited = 115,
+    NothingToClaim = 116,
+    Overflow = 117,
+    AlreadyMigrated = 118,
+    NoPendingAdmin = 119,
+    Paused = 120,
+    BuyerNotVerified = 121,
+    OrderNotFound = 122,
+    OrderExpired = 123,
+    InvalidExpiry = 124,
+    OrderNotExpired = 125,
+    SelfTrade = 126,
+    OfferNotFound = 127,
+    OfferExpired = 128,
+    OfferNotExpired = 129,
+    FundingClosed = 130,
+    FundingFailed = 131,
+    FundingNotFailed = 132,
+    FundingAlreadyFinalized = 133,
+    FundingGoalNotReached = 134,
+    NoFundingEscrow = 135,
+    NothingToRefund = 136,
+    PaymentTokenMismatch = 137,
+    SlippageExceeded = 138,
+    DeadlineExpired = 139,
+    InvalidCart = 140,
+    InvalidFeeTiers = 141,
+    InvalidReferralBps = 142,
+    InvalidReferrer = 143,
+    NothingToWithdraw = 144,
+    // 145 y 146 estan en `AuctionError`, con el mismo codigo.
+    UnsupportedPaymentToken = 147,
+    StalePrice = 148,
+    InvalidRate = 149,
+    BelowMinPurchase = 150,
+    WalletCapExceeded = 151,
+}
+
+// Errores propios de las subastas del marketplace, en el mismo rango 100.. sin
+// repetir codigos de `MarketplaceError`. Las funciones de subasta devuelven
+// `soroban_sdk::Error` para poder fallar con cualquiera de los dos enums.
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum AuctionError {
+    BidTooLow = 145,
+    InvalidAuction = 146,
+    AuctionNotFound = 152,
+    AuctionEnded = 153,
+    AuctionNotEnded = 154,
+}
+
+// Codigos en el rango 200.. para no confundirlos con los de tokenizacion (1..) y
+// marketplace (100..) cuando el error llega por una llamada cross-contract.
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum OracleError {
+    AlreadyInitialized = 200,
+    NotInitialized = 201,
+    Unauthorized = 202,
+    NoPendingAdmin = 203,
+    InvalidConfig = 204,
+    TooManyReporters = 205,
+    InvalidFeed = 206,
+    InvalidPrice = 207,
+    InvalidTimestamp = 208,
+    StalePrice = 209,
+    InsufficientReports = 210,
+}
+
+// Funciones de tokenizacion que invoca el marketplace. Las firmas deben coincidir
+// con las de `terra_tokenization`.
+#[contractclient(name = "TokenizationClient")]
+pub trait TokenizationInterface {
+    fn get_asset(env: Env, asset_id: u64) -> Result<Asset, TokenizationError>;
+    fn execute_sale(
+        env: Env,
+        seller: Address,
+        buyer: Address,
+        asset_id: u64,
+        quantity: i128,
+    ) -> Result<i128, TokenizationError>;
+    fn get_buyer_balance(env: Env, asset_id: u64, buyer: Address) -> i128;
+    fn redeem(env: Env, holder: Address, asset_id: u64, amount: i128) -> Result<(), TokenizationError>;
+    fn is_eligible(env: Env, asset_id: u64, investor: Address) -> bool;
+    fn escrow_tokens(env: Env, holder: Address, asset_id: u64, amount: i128) -> Result<(), TokenizationError>;
+    fn release_escrow(
+        env: Env,
+        holder: Address,
+        to: Address,
+        asset_id: u64,
+        amount: i128,
+    ) -> Result<(), TokenizationError>;
+    fn get_escrowed_balance(env: Env, asset_id: u64, holder: Address) -> i128;
+    fn revert_sale(env: Env, buyer: Address, asset_id: u64, quantity: i128) -> Result<(), TokenizationError>;
+}
+
+// Lo que tokenizacion consulta del marketplace: los tokens que las subastas
+// abiertas tienen reservados y que por eso no se pueden retirar de la emision.
+#[contractclient(name = "MarketplaceClient")]
+pub trait MarketplaceInterface {
+    fn get_auction_reserved(env: Env, asset_id: u64) -> i128;
+}
+
+// Interfaz de `terra_oracle` (o cualquier oraculo compatible): mediana de
+// reporters con rechazo de datos vencidos. `get_price` cotiza tokens de pago
+// alternativos con la misma escala que `PaymentRate::rate` del marketplace;
+// `get_feed` da precio y rendimiento de referencia por categoria.
+#[contractclient(name = "OracleClient")]
+pub trait OracleInterface {
+    fn get_price(env: Env, feed: String) -> Result<PriceData, OracleError>;
+    fn get_feed(env: Env, feed: String) -> Result<FeedData, OracleError>;
+}
+
+#[cfg(all(feature = "offchain", not(target_family = "wasm")))]
+pub mod offchain;
+
+mod test;
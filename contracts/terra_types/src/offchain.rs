@@ -0,0 +1,136 @@
+// Decodificacion para el backend: los valores llegan como `ScVal` en XDR base64
+// (resultado de `simulateTransaction` o data de eventos del RPC). Las vistas usan
+// strkeys para direcciones y strings para montos i128, que no entran en un
+// numero de JSON sin perder precision.
+
+extern crate std;
+
+use serde::Serialize;
+use soroban_sdk::xdr::{Error, Limits, ReadXdr, ScAddress, ScVal};
+use soroban_sdk::{Address, Env, TryFromVal, Val};
+use std::panic::{self, AssertUnwindSafe};
+use std::string::{String as StdString, ToString};
+use std::vec;
+
+use crate::{Asset, LifecycleStatus, PurchaseReceipt, WalletCap};
+
+// El host entra en panic si la forma del mapa no coincide con la del tipo; se
+// convierte en `Error::Invalid` para que un XDR de otro tipo no tumbe al backend.
+pub fn decode<T: TryFromVal<Env, Val>>(env: &Env, xdr_base64: &str) -> Result<T, Error> {
+    let scval = ScVal::from_xdr_base64(xdr_base64, Limits::none())?;
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let val = Val::try_from_val(env, &scval).map_err(|_| Error::Invalid)?;
+        T::try_from_val(env, &val).map_err(|_| Error::Invalid)
+    }))
+    .unwrap_or(Err(Error::Invalid))
+}
+
+pub fn decode_asset(xdr_base64: &str) -> Result<AssetView, Error> {
+    let env = Env::default();
+    decode::<Asset>(&env, xdr_base64).map(|asset| AssetView::from(&asset))
+}
+
+pub fn decode_receipt(xdr_base64: &str) -> Result<PurchaseReceiptView, Error> {
+    let env = Env::default();
+    decode::<PurchaseReceipt>(&env, xdr_base64).map(|receipt| PurchaseReceiptView::from(&receipt))
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AssetView {
+    pub id: u64,
+    pub seller: StdString,
+    pub category: StdString,
+    pub title: StdString,
+    pub price_per_token: StdString,
+    pub total_tokens: StdString,
+    pub available_tokens: StdString,
+    pub circulating_supply: StdString,
+    pub active: bool,
+    pub lifecycle_status: &'static str,
+    pub cycle_duration_days: u32,
+    pub cycle_start_at: u64,
+    pub cycle_end_at: u64,
+    pub funding_goal: StdString,
+    pub funding_deadline: u64,
+    pub min_purchase: StdString,
+    pub max_per_wallet_tokens: Option<StdString>,
+    pub max_per_wallet_bps: Option<u32>,
+}
+
+impl From<&Asset> for AssetView {
+    fn from(asset: &Asset) -> Self {
+        Self {
+            id: asset.id,
+            seller: address(&asset.seller),
+            category: text(&asset.category),
+            title: text(&asset.title),
+            price_per_token: asset.price_per_token.to_string(),
+            total_tokens: asset.total_tokens.to_string(),
+            available_tokens: asset.available_tokens.to_string(),
+            circulating_supply: asset.circulating_supply.to_string(),
+            active: asset.active,
+            lifecycle_status: match asset.lifecycle_status {
+                LifecycleStatus::Funding => "funding",
+                LifecycleStatus::Operating => "operating",
+                LifecycleStatus::Settled => "settled",
+            },
+            cycle_duration_days: asset.cycle_duration_days,
+            cycle_start_at: asset.cycle_start_at,
+            cycle_end_at: asset.cycle_end_at,
+            funding_goal: asset.funding_goal.to_string(),
+            funding_deadline: asset.funding_deadline,
+            min_purchase: asset.min_purchase.to_string(),
+            max_per_wallet_tokens: match asset.max_per_wallet {
+                WalletCap::Tokens(tokens) => Some(tokens.to_string()),
+                _ => None,
+            },
+            max_per_wallet_bps: match asset.max_per_wallet {
+                WalletCap::Bps(bps) => Some(bps),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PurchaseReceiptView {
+    pub asset_id: u64,
+    pub seller: StdString,
+    pub buyer: StdString,
+    pub quantity: StdString,
+    pub total_paid: StdString,
+    pub fee_paid: StdString,
+    pub seller_amount: StdString,
+    pub referrer: Option<StdString>,
+    pub referral_amount: StdString,
+    pub payment_token: StdString,
+    pub quoted_total: StdString,
+}
+
+impl From<&PurchaseReceipt> for PurchaseReceiptView {
+    fn from(receipt: &PurchaseReceipt) -> Self {
+        Self {
+            asset_id: receipt.asset_id,
+            seller: address(&receipt.seller),
+            buyer: address(&receipt.buyer),
+            quantity: receipt.quantity.to_string(),
+            total_paid: receipt.total_paid.to_string(),
+            fee_paid: receipt.fee_paid.to_string(),
+            seller_amount: receipt.seller_amount.to_string(),
+            referrer: receipt.referrer.as_ref().map(address),
+            referral_amount: receipt.referral_amount.to_string(),
+            payment_token: address(&receipt.payment_token),
+            quoted_total: receipt.quoted_total.to_string(),
+        }
+    }
+}
+
+fn address(address: &Address) -> StdString {
+    ScAddress::from(address).to_string()
+}
+
+fn text(value: &soroban_sdk::String) -> StdString {
+    let mut buf = vec![0_u8; value.len() as usize];
+    value.copy_into_slice(&mut buf);
+    StdString::from_utf8_lossy(&buf).into_owned()
+}
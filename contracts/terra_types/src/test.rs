@@ -0,0 +1,44 @@
+#![cfg(all(test, feature = "offchain"))]
+
+use super::*;
+use soroban_sdk::testutils::Address as _;
+use soroban_sdk::xdr::{Limits, ScVal, WriteXdr};
+use soroban_sdk::{IntoVal, TryFromVal, Val};
+
+#[test]
+fn backend_decodes_asset_xdr() {
+    let env = Env::default();
+    let asset = Asset {
+        id: 7,
+        seller: Address::generate(&env),
+        category: String::from_str(&env, "ganaderia"),
+        title: String::from_str(&env, "Novillos lote 2"),
+        price_per_token: 12_345_678_901_234_567,
+        total_tokens: 1_000,
+        available_tokens: 400,
+        circulating_supply: 600,
+        active: true,
+        lifecycle_status: LifecycleStatus::Operating,
+        cycle_duration_days: 180,
+        cycle_start_at: 1_700_000_000,
+        cycle_end_at: 1_715_552_000,
+        funding_goal: 0,
+        funding_deadline: 0,
+        min_purchase: 10,
+        max_per_wallet: WalletCap::Bps(500),
+    };
+    let val: Val = asset.into_val(&env);
+    let xdr = ScVal::try_from_val(&env, &val)
+        .unwrap()
+        .to_xdr_base64(Limits::none())
+        .unwrap();
+
+    let view = offchain::decode_asset(&xdr).unwrap();
+    assert_eq!(view.id, 7);
+    assert!(view.seller.starts_with('C') && view.seller.len() == 56);
+    assert_eq!(view.title, "Novillos lote 2");
+    assert_eq!(view.price_per_token, "12345678901234567");
+    assert_eq!(view.lifecycle_status, "operating");
+    assert_eq!((view.max_per_wallet_tokens, view.max_per_wallet_bps), (None, Some(500)));
+    assert!(offchain::decode_receipt(&xdr).is_err());
+}